@@ -0,0 +1,34 @@
+use std::{
+    env::args,
+    io::{read_to_string, stdin},
+    process::exit,
+};
+
+use jumperless_common::frame::{parse_dump, render_svg, render_terminal};
+
+fn main() {
+    let svg = match args().nth(1).as_deref() {
+        None => false,
+        Some("--svg") => true,
+        Some(_) => {
+            eprintln!("Usage: render-leds [--svg] < dump.txt");
+            eprintln!();
+            eprintln!("Reads the output of the `dump-leds` shell command from stdin, and draws the LEDs");
+            eprintln!("to the terminal (or as SVG, with --svg)");
+            exit(-1);
+        }
+    };
+
+    let input = read_to_string(stdin()).expect("read stdin");
+    // the dump may be surrounded by other output (like the shell prompt)
+    let Some(colors) = input.lines().filter_map(parse_dump).find(|colors| !colors.is_empty()) else {
+        eprintln!("No LED dump found in input");
+        exit(-1);
+    };
+
+    if svg {
+        print!("{}", render_svg(&colors));
+    } else {
+        print!("{}", render_terminal(&colors));
+    }
+}
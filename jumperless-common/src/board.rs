@@ -1,9 +1,15 @@
 #[cfg(feature = "board-v4")]
 mod v4;
 #[cfg(feature = "board-v4")]
-pub use v4::{init_board, Board, BoardSpec, Node};
+pub use v4::{init_board, leds, Board, BoardSpec, Node};
+/// Hardware revision of the board
+#[cfg(feature = "board-v4")]
+pub const REVISION: &str = "v4";
 
 #[cfg(feature = "board-v5")]
 mod v5;
 #[cfg(feature = "board-v5")]
-pub use v5::{init_board, Board, BoardSpec, Node};
+pub use v5::{init_board, leds, Board, BoardSpec, Node};
+/// Hardware revision of the board
+#[cfg(feature = "board-v5")]
+pub const REVISION: &str = "v5";
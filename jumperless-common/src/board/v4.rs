@@ -129,6 +129,106 @@ impl NodeTrait for Node {
     }
 }
 impl Node {
+    pub const ALL: [Node; 98usize] = [
+        Node::_1,
+        Node::_2,
+        Node::_3,
+        Node::_4,
+        Node::_5,
+        Node::_6,
+        Node::_7,
+        Node::_8,
+        Node::_9,
+        Node::_10,
+        Node::_11,
+        Node::_12,
+        Node::_13,
+        Node::_14,
+        Node::_15,
+        Node::_16,
+        Node::_17,
+        Node::_18,
+        Node::_19,
+        Node::_20,
+        Node::_21,
+        Node::_22,
+        Node::_23,
+        Node::_24,
+        Node::_25,
+        Node::_26,
+        Node::_27,
+        Node::_28,
+        Node::_29,
+        Node::_30,
+        Node::_31,
+        Node::_32,
+        Node::_33,
+        Node::_34,
+        Node::_35,
+        Node::_36,
+        Node::_37,
+        Node::_38,
+        Node::_39,
+        Node::_40,
+        Node::_41,
+        Node::_42,
+        Node::_43,
+        Node::_44,
+        Node::_45,
+        Node::_46,
+        Node::_47,
+        Node::_48,
+        Node::_49,
+        Node::_50,
+        Node::_51,
+        Node::_52,
+        Node::_53,
+        Node::_54,
+        Node::_55,
+        Node::_56,
+        Node::_57,
+        Node::_58,
+        Node::_59,
+        Node::_60,
+        Node::NANO_A0,
+        Node::NANO_D1,
+        Node::NANO_A2,
+        Node::NANO_D3,
+        Node::NANO_A4,
+        Node::NANO_D5,
+        Node::NANO_A6,
+        Node::NANO_D7,
+        Node::NANO_D11,
+        Node::NANO_D9,
+        Node::NANO_D13,
+        Node::NANO_RESET,
+        Node::DAC0,
+        Node::DAC1,
+        Node::ADC0,
+        Node::ADC1,
+        Node::ADC2,
+        Node::ADC3,
+        Node::SUPPLY_3V3,
+        Node::GND,
+        Node::NANO_D0,
+        Node::NANO_A1,
+        Node::NANO_D2,
+        Node::NANO_A3,
+        Node::NANO_D4,
+        Node::NANO_A5,
+        Node::NANO_D6,
+        Node::NANO_A7,
+        Node::NANO_D8,
+        Node::NANO_D10,
+        Node::NANO_D12,
+        Node::NANO_AREF,
+        Node::SUPPLY_5V,
+        Node::ISENSE_MINUS,
+        Node::ISENSE_PLUS,
+        Node::RP_UART_TX,
+        Node::RP_UART_RX,
+        Node::RP_GPIO0,
+    ];
     pub fn as_str(&self) -> &'static str {
         match self {
             Node::_1 => "1",
@@ -231,6 +331,113 @@ impl Node {
             Node::RP_GPIO0 => "RP_GPIO0",
         }
     }
+    pub fn leds(&self) -> &'static [usize] {
+        match self {
+            Node::_1 => &[0usize],
+            Node::_2 => &[1usize],
+            Node::_3 => &[2usize],
+            Node::_4 => &[3usize],
+            Node::_5 => &[4usize],
+            Node::_6 => &[5usize],
+            Node::_7 => &[6usize],
+            Node::_8 => &[7usize],
+            Node::_9 => &[8usize],
+            Node::_10 => &[9usize],
+            Node::_11 => &[10usize],
+            Node::_12 => &[11usize],
+            Node::_13 => &[12usize],
+            Node::_14 => &[13usize],
+            Node::_15 => &[14usize],
+            Node::_16 => &[15usize],
+            Node::_17 => &[16usize],
+            Node::_18 => &[17usize],
+            Node::_19 => &[18usize],
+            Node::_20 => &[19usize],
+            Node::_21 => &[20usize],
+            Node::_22 => &[21usize],
+            Node::_23 => &[22usize],
+            Node::_24 => &[23usize],
+            Node::_25 => &[24usize],
+            Node::_26 => &[25usize],
+            Node::_27 => &[26usize],
+            Node::_28 => &[27usize],
+            Node::_29 => &[28usize],
+            Node::_30 => &[29usize],
+            Node::_31 => &[30usize],
+            Node::_32 => &[31usize],
+            Node::_33 => &[32usize],
+            Node::_34 => &[33usize],
+            Node::_35 => &[34usize],
+            Node::_36 => &[35usize],
+            Node::_37 => &[36usize],
+            Node::_38 => &[37usize],
+            Node::_39 => &[38usize],
+            Node::_40 => &[39usize],
+            Node::_41 => &[40usize],
+            Node::_42 => &[41usize],
+            Node::_43 => &[42usize],
+            Node::_44 => &[43usize],
+            Node::_45 => &[44usize],
+            Node::_46 => &[45usize],
+            Node::_47 => &[46usize],
+            Node::_48 => &[47usize],
+            Node::_49 => &[48usize],
+            Node::_50 => &[49usize],
+            Node::_51 => &[50usize],
+            Node::_52 => &[51usize],
+            Node::_53 => &[52usize],
+            Node::_54 => &[53usize],
+            Node::_55 => &[54usize],
+            Node::_56 => &[55usize],
+            Node::_57 => &[56usize],
+            Node::_58 => &[57usize],
+            Node::_59 => &[58usize],
+            Node::_60 => &[59usize],
+            Node::NANO_A0 => &[98usize],
+            Node::NANO_D1 => &[80usize],
+            Node::NANO_A2 => &[100usize],
+            Node::NANO_D3 => &[85usize],
+            Node::NANO_A4 => &[102usize],
+            Node::NANO_D5 => &[87usize],
+            Node::NANO_A6 => &[104usize],
+            Node::NANO_D7 => &[89usize],
+            Node::NANO_D11 => &[93usize],
+            Node::NANO_D9 => &[91usize],
+            Node::NANO_D13 => &[95usize],
+            Node::NANO_RESET => &[107usize],
+            Node::SUPPLY_3V3 => &[96usize],
+            Node::GND => &[
+                83usize, 108usize, 60usize, 63usize, 64usize, 67usize, 68usize, 71usize, 72usize,
+                75usize, 76usize, 79usize,
+            ],
+            Node::NANO_D0 => &[81usize],
+            Node::NANO_A1 => &[99usize],
+            Node::NANO_D2 => &[84usize],
+            Node::NANO_A3 => &[101usize],
+            Node::NANO_D4 => &[86usize],
+            Node::NANO_A5 => &[103usize],
+            Node::NANO_D6 => &[88usize],
+            Node::NANO_A7 => &[105usize],
+            Node::NANO_D8 => &[90usize],
+            Node::NANO_D10 => &[92usize],
+            Node::NANO_D12 => &[94usize],
+            Node::NANO_AREF => &[97usize],
+            Node::SUPPLY_5V => &[106usize, 109usize],
+            _ => &[],
+        }
+    }
+}
+pub mod leds {
+    pub const COUNT: usize = 111usize;
+    pub const TOP_RAIL: &[usize] = &[70usize, 73usize, 74usize, 77usize, 78usize];
+    pub const BOTTOM_RAIL: &[usize] = &[61usize, 62usize, 65usize, 66usize, 69usize];
+    pub const HEADER: &[usize] = &[
+        80usize, 81usize, 82usize, 83usize, 84usize, 85usize, 86usize, 87usize, 88usize, 89usize,
+        90usize, 91usize, 92usize, 93usize, 94usize, 95usize, 96usize, 97usize, 98usize, 99usize,
+        100usize, 101usize, 102usize, 103usize, 104usize, 105usize, 106usize, 107usize, 108usize,
+        109usize,
+    ];
+    pub const LOGO: &[usize] = &[110usize];
 }
 #[derive(Debug)]
 pub struct InvalidNode;
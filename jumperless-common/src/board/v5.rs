@@ -138,6 +138,115 @@ impl NodeTrait for Node {
     }
 }
 impl Node {
+    pub const ALL: [Node; 107usize] = [
+        Node::GND,
+        Node::SUPPLY_5V,
+        Node::_1,
+        Node::_2,
+        Node::_3,
+        Node::_4,
+        Node::_5,
+        Node::_6,
+        Node::_7,
+        Node::_8,
+        Node::_9,
+        Node::_10,
+        Node::_11,
+        Node::_12,
+        Node::_13,
+        Node::_14,
+        Node::_15,
+        Node::_16,
+        Node::_17,
+        Node::_18,
+        Node::_19,
+        Node::_20,
+        Node::_21,
+        Node::_22,
+        Node::_23,
+        Node::_24,
+        Node::_25,
+        Node::_26,
+        Node::_27,
+        Node::_28,
+        Node::_29,
+        Node::_30,
+        Node::_31,
+        Node::_32,
+        Node::_33,
+        Node::_34,
+        Node::_35,
+        Node::_36,
+        Node::_37,
+        Node::_38,
+        Node::_39,
+        Node::_40,
+        Node::_41,
+        Node::_42,
+        Node::_43,
+        Node::_44,
+        Node::_45,
+        Node::_46,
+        Node::_47,
+        Node::_48,
+        Node::_49,
+        Node::_50,
+        Node::_51,
+        Node::_52,
+        Node::_53,
+        Node::_54,
+        Node::_55,
+        Node::_56,
+        Node::_57,
+        Node::_58,
+        Node::_59,
+        Node::_60,
+        Node::NANO_D0,
+        Node::NANO_D1,
+        Node::NANO_D2,
+        Node::NANO_D3,
+        Node::NANO_D4,
+        Node::NANO_D5,
+        Node::NANO_D6,
+        Node::NANO_D7,
+        Node::NANO_D8,
+        Node::NANO_D9,
+        Node::NANO_D10,
+        Node::NANO_D11,
+        Node::NANO_D12,
+        Node::NANO_D13,
+        Node::NANO_A0,
+        Node::NANO_A1,
+        Node::NANO_A2,
+        Node::NANO_A3,
+        Node::NANO_A4,
+        Node::NANO_A5,
+        Node::NANO_A6,
+        Node::NANO_A7,
+        Node::RP_UART_RX,
+        Node::RP_UART_TX,
+        Node::ISENSE_PLUS,
+        Node::ISENSE_MINUS,
+        Node::TOP_RAIL,
+        Node::BOTTOM_RAIL,
+        Node::DAC1,
+        Node::DAC0,
+        Node::ADC0,
+        Node::ADC1,
+        Node::ADC2,
+        Node::ADC3,
+        Node::NANO_RESET_J0,
+        Node::NANO_RESET_J1,
+        Node::NANO_AREF,
+        Node::GPIO_20,
+        Node::GPIO_21,
+        Node::GPIO_22,
+        Node::GPIO_23,
+        Node::GPIO_MCP_0,
+        Node::GPIO_MCP_1,
+        Node::GPIO_MCP_2,
+        Node::GPIO_MCP_3,
+    ];
     pub fn as_str(&self) -> &'static str {
         match self {
             Node::GND => "GND",
@@ -249,6 +358,16 @@ impl Node {
             Node::GPIO_MCP_3 => "GPIO_MCP_3",
         }
     }
+    pub fn leds(&self) -> &'static [usize] {
+        &[]
+    }
+}
+pub mod leds {
+    pub const COUNT: usize = 0usize;
+    pub const TOP_RAIL: &[usize] = &[];
+    pub const BOTTOM_RAIL: &[usize] = &[];
+    pub const HEADER: &[usize] = &[];
+    pub const LOGO: &[usize] = &[];
 }
 #[derive(Debug)]
 pub struct InvalidNode;
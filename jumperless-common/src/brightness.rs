@@ -0,0 +1,162 @@
+//! Global brightness of the LEDs, and limiting the current they draw
+//!
+//! LED colors are scaled by a linear factor while they are written to the LEDs, [`FULL_SCALE`] being full brightness.
+//! The factor is 16 bits wide, so that low brightness settings still differ from each other (and from "off").
+//! If the scaled colors would draw more current than a given budget, the factor is reduced further (see [`limit_scale`]).
+
+/// Scaling factor of full brightness
+pub const FULL_SCALE: u32 = 1 << 16;
+
+/// Scaling factor for each brightness percentage, following a gamma curve, so that the brightness is perceived linearly
+///
+/// Computed as `round((percent / 100)^2.2 * FULL_SCALE)`, but at least 1 for any percentage above zero.
+const GAMMA_TABLE: [u32; 101] = [
+    0, 3, 12, 29, 55, 90, 134, 189, 253, 328, 414, 510, 618, 736, 867, 1009, 1163, 1329, 1507, 1697,
+    1900, 2115, 2343, 2584, 2838, 3104, 3384, 3677, 3983, 4303, 4636, 4983, 5343, 5718, 6106, 6508,
+    6924, 7354, 7798, 8257, 8730, 9217, 9719, 10236, 10767, 11312, 11873, 12448, 13038, 13643,
+    14263, 14898, 15548, 16214, 16895, 17590, 18302, 19029, 19771, 20528, 21302, 22091, 22895,
+    23715, 24551, 25403, 26271, 27155, 28054, 28970, 29902, 30850, 31813, 32794, 33790, 34803,
+    35832, 36877, 37939, 39018, 40112, 41224, 42352, 43496, 44657, 45835, 47030, 48242, 49470,
+    50715, 51977, 53256, 54552, 55865, 57195, 58543, 59907, 61288, 62687, 64103, 65536,
+];
+
+/// Converts a perceived brightness (in percent, 0 - 100) to a linear scaling factor
+///
+/// Any brightness above zero results in a non-zero factor. Percentages above 100 are treated as 100.
+pub fn brightness_scale(percent: u8) -> u32 {
+    GAMMA_TABLE[percent.min(100) as usize]
+}
+
+/// Scales a color component by `scale / FULL_SCALE`
+///
+/// Rounds up, so that dim colors don't disappear entirely, unless the scale is zero. Saturates at full intensity.
+pub fn scale_component(component: u8, scale: u32) -> u8 {
+    (component as u64 * scale as u64).div_ceil(FULL_SCALE as u64).min(0xFF) as u8
+}
+
+/// Estimated current drawn by a single color component, per step (of 255), in µA
+///
+/// A WS2812 draws about 20mA per color component at full intensity.
+pub const UA_PER_STEP: u32 = 20_000 / 255;
+
+/// Estimated current (in µA) drawn by LEDs with the given color `components`, once scaled by `scale`
+///
+/// This only accounts for current drawn by lit LEDs, not the quiescent current of each LED.
+pub fn estimate_current(components: impl IntoIterator<Item = u8>, scale: u32) -> u32 {
+    components
+        .into_iter()
+        .map(|c| scale_component(c, scale) as u32 * UA_PER_STEP)
+        .sum()
+}
+
+/// Reduce `scale` as far as needed, so that LEDs with the given color `components` draw at most `budget`
+/// milliamps (see [`estimate_current`])
+///
+/// Returns `scale` unchanged if the LEDs stay within the budget anyway.
+pub fn limit_scale(components: impl IntoIterator<Item = u8>, scale: u32, budget: u32) -> u32 {
+    let budget_steps = budget as u64 * 1000 / UA_PER_STEP as u64;
+    // sum of all color components, number of components which are lit, and steps drawn at the requested scale
+    let (sum, lit, steps) = components.into_iter().fold((0u64, 0u64, 0u64), |(sum, lit, steps), c| {
+        (sum + c as u64, lit + (c > 0) as u64, steps + scale_component(c, scale) as u64)
+    });
+    if steps <= budget_steps {
+        return scale;
+    }
+    // `scale_component` rounds up, which adds up to one step for every lit component
+    (budget_steps.saturating_sub(lit) * FULL_SCALE as u64 / sum) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gamma_table() {
+        for percent in 1..=100u8 {
+            let expected = ((percent as f64 / 100.0).powf(2.2) * FULL_SCALE as f64).round().max(1.0);
+            assert_eq!(brightness_scale(percent) as f64, expected, "{}%", percent);
+        }
+        assert_eq!(brightness_scale(0), 0);
+        assert_eq!(brightness_scale(100), FULL_SCALE);
+        assert_eq!(brightness_scale(255), FULL_SCALE);
+    }
+
+    #[test]
+    fn test_low_brightness() {
+        // not dark, even at 1%
+        assert!(brightness_scale(1) > 0);
+        assert_eq!(scale_component(0xFF, brightness_scale(1)), 1);
+        // the ratio between components is kept, once they are bright enough
+        let scale = brightness_scale(20);
+        assert!(scale_component(0xFF, scale) > scale_component(0x40, scale));
+    }
+
+    #[test]
+    fn test_monotonic() {
+        for percent in 0..100u8 {
+            assert!(brightness_scale(percent) <= brightness_scale(percent + 1), "{}%", percent);
+            assert!(
+                scale_component(0x80, brightness_scale(percent)) <= scale_component(0x80, brightness_scale(percent + 1))
+            );
+        }
+    }
+
+    #[test]
+    fn test_scale_component() {
+        assert_eq!(scale_component(0xFF, FULL_SCALE), 0xFF);
+        assert_eq!(scale_component(0x80, FULL_SCALE / 2), 0x40);
+        assert_eq!(scale_component(0x01, 1), 1);
+        assert_eq!(scale_component(0xFF, 0), 0);
+        assert_eq!(scale_component(0xFF, 2 * FULL_SCALE), 0xFF);
+    }
+
+    /// Components of `count` LEDs, all with the given color
+    fn leds(count: usize, (r, g, b): (u8, u8, u8)) -> Vec<u8> {
+        [r, g, b].repeat(count)
+    }
+
+    #[test]
+    fn test_within_budget() {
+        let components = leds(10, (0xFF, 0x80, 0));
+        assert_eq!(limit_scale(components.iter().copied(), FULL_SCALE, 1000), FULL_SCALE);
+        assert_eq!(limit_scale(leds(300, (0, 0, 0)), FULL_SCALE, 0), FULL_SCALE);
+    }
+
+    #[test]
+    fn test_limit_scale() {
+        // 100 white LEDs draw about 6A at full brightness
+        let components = leds(100, (0xFF, 0xFF, 0xFF));
+        assert_eq!(estimate_current(components.iter().copied(), FULL_SCALE), 100 * 3 * 255 * UA_PER_STEP);
+        for (scale, budget) in [(FULL_SCALE, 300), (FULL_SCALE, 1000), (brightness_scale(50), 300)] {
+            let limited = limit_scale(components.iter().copied(), scale, budget);
+            assert!(limited < scale);
+            assert!(estimate_current(components.iter().copied(), limited) <= budget * 1000);
+            // not limited further than needed (apart from the rounding slack)
+            let slack = 300 * UA_PER_STEP;
+            assert!(estimate_current(components.iter().copied(), limited + 1) + slack > budget * 1000);
+        }
+    }
+
+    #[test]
+    fn test_rounding_slack() {
+        // dim components draw a full step (46.8mA in total), unless they are turned off entirely
+        let dim = leds(200, (1, 1, 1));
+        assert_eq!(estimate_current(dim.iter().copied(), 1), 600 * UA_PER_STEP);
+        assert_eq!(limit_scale(dim.iter().copied(), FULL_SCALE, 50), FULL_SCALE);
+        // without the slack, a scale of ~85% would be picked, which still draws 46.8mA
+        assert_eq!(limit_scale(dim.iter().copied(), FULL_SCALE, 40), 0);
+
+        // bright components are scaled down further, to make up for the dim ones
+        let mixed = [leds(50, (0xFF, 0, 0)), dim].concat();
+        let limited = limit_scale(mixed.iter().copied(), FULL_SCALE, 200);
+        assert!(limited > 0);
+        assert!(estimate_current(mixed.iter().copied(), limited) <= 200_000);
+    }
+
+    #[test]
+    fn test_zero_budget() {
+        let components = leds(1, (0, 0x10, 0));
+        assert_eq!(limit_scale(components.iter().copied(), FULL_SCALE, 0), 0);
+        assert_eq!(estimate_current(components.iter().copied(), 0), 0);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{board::Node, Millivolts};
+use jumperless_types::{Net, NetId};
+
+/// An RGB color
+pub type Color = (u8, u8, u8);
+
+/// Named colors, accepted by [`parse_color`]
+const NAMED_COLORS: &[(&str, Color)] = &[
+    ("red", (0xFF, 0x00, 0x00)),
+    ("orange", (0xFF, 0x50, 0x00)),
+    ("yellow", (0xFF, 0xC8, 0x00)),
+    ("lime", (0x80, 0xFF, 0x00)),
+    ("green", (0x00, 0xFF, 0x00)),
+    ("cyan", (0x00, 0xFF, 0xFF)),
+    ("blue", (0x00, 0x00, 0xFF)),
+    ("purple", (0x78, 0x00, 0xFF)),
+    ("magenta", (0xFF, 0x00, 0xFF)),
+    ("pink", (0xFF, 0x00, 0x64)),
+    ("white", (0xFF, 0xFF, 0xFF)),
+];
+
+/// Parse a color, given either as `#rrggbb` or by name (like `red`)
+pub fn parse_color(input: &str) -> Option<Color> {
+    if let Some(hex) = input.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(input))
+        .map(|(_, color)| *color)
+}
+
+/// Parse a color given as `rrggbb`
+pub(crate) fn parse_hex(hex: &str) -> Option<Color> {
+    // `from_str_radix` alone would accept a sign, like in `+f+f+f`
+    if hex.len() != 6 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((component(0)?, component(2)?, component(4)?))
+}
+
+/// Set of colors to pick net colors from
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Palette {
+    /// Random colors. Not deterministic, so not handled by [`pick_net_color`].
+    Random,
+    /// Saturated colors which are easy to tell apart
+    Contrast,
+    /// Hues evenly spread around the color wheel
+    Rainbow,
+}
+
+const CONTRAST: &[Color] = &[
+    (0xFF, 0x00, 0x00),
+    (0x00, 0xFF, 0x00),
+    (0x00, 0x00, 0xFF),
+    (0xFF, 0xC8, 0x00),
+    (0xFF, 0x00, 0xFF),
+    (0x00, 0xFF, 0xFF),
+    (0xFF, 0x50, 0x00),
+    (0x78, 0x00, 0xFF),
+    (0x80, 0xFF, 0x00),
+    (0xFF, 0x00, 0x64),
+    (0x00, 0x78, 0xFF),
+    (0xFF, 0xFF, 0xFF),
+];
+
+const RAINBOW: &[Color] = &[
+    (0xFF, 0x00, 0x00),
+    (0xFF, 0x80, 0x00),
+    (0xFF, 0xFF, 0x00),
+    (0x80, 0xFF, 0x00),
+    (0x00, 0xFF, 0x00),
+    (0x00, 0xFF, 0x80),
+    (0x00, 0xFF, 0xFF),
+    (0x00, 0x80, 0xFF),
+    (0x00, 0x00, 0xFF),
+    (0x80, 0x00, 0xFF),
+    (0xFF, 0x00, 0xFF),
+    (0xFF, 0x00, 0x80),
+];
+
+const PALETTE_RANDOM: &str = "random";
+const PALETTE_CONTRAST: &str = "contrast";
+const PALETTE_RAINBOW: &str = "rainbow";
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Random, Palette::Contrast, Palette::Rainbow];
+
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            PALETTE_RANDOM => Some(Palette::Random),
+            PALETTE_CONTRAST => Some(Palette::Contrast),
+            PALETTE_RAINBOW => Some(Palette::Rainbow),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Palette::Random => PALETTE_RANDOM,
+            Palette::Contrast => PALETTE_CONTRAST,
+            Palette::Rainbow => PALETTE_RAINBOW,
+        }
+    }
+
+    /// Colors of this palette (empty for [`Palette::Random`])
+    pub fn colors(&self) -> &'static [Color] {
+        match self {
+            Palette::Random => &[],
+            Palette::Contrast => CONTRAST,
+            Palette::Rainbow => RAINBOW,
+        }
+    }
+}
+
+/// Stops of the gradient used by [`voltage_color`], ordered by voltage
+///
+/// The colors for GND, 3.3V and 5V match the ones of the corresponding special nets.
+const VOLTAGE_GRADIENT: &[(Millivolts, Color)] = &[
+    (-8000, (0x12, 0x09, 0x32)),
+    (0, (0x00, 0x1c, 0x04)),
+    (3300, (0x1c, 0x01, 0x07)),
+    (5000, (0x1c, 0x07, 0x02)),
+    (8000, (0x30, 0x1A, 0x02)),
+];
+
+/// Color representing the given voltage
+///
+/// Interpolates linearly between the stops of a gradient. Voltages beyond either end get the color of that end.
+pub fn voltage_color(voltage: Millivolts) -> Color {
+    let (first, last) = (VOLTAGE_GRADIENT[0], VOLTAGE_GRADIENT[VOLTAGE_GRADIENT.len() - 1]);
+    if voltage <= first.0 {
+        return first.1;
+    }
+    if voltage >= last.0 {
+        return last.1;
+    }
+    let (low, high) = VOLTAGE_GRADIENT
+        .windows(2)
+        .map(|stops| (stops[0], stops[1]))
+        .find(|(_, high)| voltage < high.0)
+        .unwrap();
+    let mix = |a: u8, b: u8| {
+        let (a, b) = (a as i32, b as i32);
+        (a + (b - a) * (voltage - low.0) / (high.0 - low.0)) as u8
+    };
+    (mix(low.1 .0, high.1 .0), mix(low.1 .1, high.1 .1), mix(low.1 .2, high.1 .2))
+}
+
+/// Approximate perceived (squared) distance between two colors
+///
+/// Uses the "redmean" approximation, which weighs the components depending on how red the colors are.
+pub fn distance(a: Color, b: Color) -> u32 {
+    let rmean = (a.0 as i32 + b.0 as i32) / 2;
+    let dr = a.0 as i32 - b.0 as i32;
+    let dg = a.1 as i32 - b.1 as i32;
+    let db = a.2 as i32 - b.2 as i32;
+    ((((512 + rmean) * dr * dr) >> 8) + 4 * dg * dg + (((767 - rmean) * db * db) >> 8)) as u32
+}
+
+/// Row of a breadboard node (1 - 60)
+fn breadboard_row(node: Node) -> Option<u8> {
+    node.as_str().parse().ok()
+}
+
+/// Are the given breadboard rows next to each other?
+///
+/// Rows 1 - 30 form the top half of the breadboard, 31 - 60 the bottom half.
+/// Row `n` of the top half sits across the gap from row `n + 30`.
+fn rows_adjacent(a: u8, b: u8) -> bool {
+    let same_half = (a <= 30) == (b <= 30);
+    (same_half && a.abs_diff(b) == 1) || a.abs_diff(b) == 30
+}
+
+fn nets_adjacent(a: &Net<Node>, b: &Net<Node>) -> bool {
+    a.nodes.iter().filter_map(breadboard_row).any(|row_a| {
+        b.nodes
+            .iter()
+            .filter_map(breadboard_row)
+            .any(|row_b| rows_adjacent(row_a, row_b))
+    })
+}
+
+/// Pick a color for the given net from the palette
+///
+/// Picks the color which differs most from the colors of nets on adjacent breadboard rows,
+/// and among those the one that differs most from all other nets. Ties are resolved by palette
+/// order, so the outcome is deterministic.
+///
+/// `colors` holds the color of each net in `nets`, at the same index.
+/// Returns `None` for palettes without fixed colors.
+pub fn pick_net_color(palette: Palette, nets: &[Net<Node>], colors: &[Color], net_id: NetId) -> Option<Color> {
+    let net = &nets[net_id.index()];
+    let others = || {
+        nets.iter()
+            .zip(colors)
+            .filter(|(other, _)| other.id != net_id && !other.nodes.is_empty())
+    };
+    palette
+        .colors()
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(index, candidate)| {
+            let neighbor_distance = others()
+                .filter(|(other, _)| nets_adjacent(net, other))
+                .map(|(_, color)| distance(*candidate, *color))
+                .min()
+                .unwrap_or(u32::MAX);
+            let distance = others()
+                .map(|(_, color)| distance(*candidate, *color))
+                .min()
+                .unwrap_or(u32::MAX);
+            // prefer earlier colors on ties
+            (neighbor_distance, distance, usize::MAX - index)
+        })
+        .map(|(_, color)| color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000"), Some((0xFF, 0x80, 0x00)));
+        assert_eq!(parse_color("#00A0fF"), Some((0x00, 0xA0, 0xFF)));
+        assert_eq!(parse_color("Red"), Some((0xFF, 0x00, 0x00)));
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+        assert_eq!(parse_color("#+f+f+f"), None);
+        assert_eq!(parse_color("#-1-1-1"), None);
+        assert_eq!(parse_color("brown"), None);
+    }
+
+    #[test]
+    fn test_voltage_color() {
+        assert_eq!(voltage_color(5000), (0x1c, 0x07, 0x02));
+        assert_eq!(voltage_color(-12000), voltage_color(-8000));
+        assert_eq!(voltage_color(20000), voltage_color(8000));
+        // halfway between 5V and 8V
+        assert_eq!(voltage_color(6500), (0x26, 0x10, 0x02));
+        assert_ne!(voltage_color(-5000), voltage_color(5000));
+    }
+
+    #[test]
+    fn test_rows_adjacent() {
+        assert!(rows_adjacent(3, 4));
+        assert!(rows_adjacent(4, 3));
+        assert!(rows_adjacent(5, 35));
+        assert!(!rows_adjacent(30, 31));
+        assert!(!rows_adjacent(3, 5));
+    }
+
+    #[test]
+    fn test_pick_net_color() {
+        let nets = [
+            Net::from_iter(1.into(), [Node::_3].into_iter()),
+            Net::from_iter(2.into(), [Node::_10].into_iter()),
+            Net::from_iter(3.into(), [Node::_4].into_iter()),
+        ];
+        let red = (0xFF, 0x00, 0x00);
+        let green = (0x00, 0xFF, 0x00);
+        let colors = [red, green, (0, 0, 0)];
+        let color = pick_net_color(Palette::Contrast, &nets, &colors, 3.into()).unwrap();
+        // the neighbor (row 3) is red, so anything but red
+        assert_ne!(color, red);
+        assert!(distance(color, red) >= distance(green, red));
+        assert_eq!(pick_net_color(Palette::Random, &nets, &colors, 3.into()), None);
+    }
+
+    #[test]
+    fn test_pick_net_color_deterministic() {
+        let nets = [Net::from_iter(1.into(), [Node::_3].into_iter())];
+        let colors = [(0, 0, 0)];
+        assert_eq!(
+            pick_net_color(Palette::Rainbow, &nets, &colors, 1.into()),
+            Some(RAINBOW[0])
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Records of why the device crashed
+//!
+//! A crash record is written to RAM which survives resets (and then to flash) by the firmware. This module contains
+//! the record itself, and its binary encoding.
+
+use core::fmt::Write;
+
+/// Maximum length of a crash message, longer ones are truncated
+pub const MAX_MESSAGE: usize = 120;
+
+/// Marks a valid crash record. Bump the last byte when the layout changes incompatibly.
+const MAGIC: [u8; 4] = *b"JLC\x01";
+
+/// Size of an encoded crash record
+pub const ENCODED_SIZE: usize = 128;
+
+/// Offset of the message in an encoded crash record, after the magic, kind and message length
+const MESSAGE_OFFSET: usize = 6;
+
+/// What caused a crash
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrashKind {
+    Panic,
+    /// A supervised task stalled (see `task::watchdog` in the firmware)
+    TaskStalled,
+    /// The hardware watchdog reset the device, without recording a reason first
+    WatchdogTimeout,
+}
+
+impl CrashKind {
+    const ALL: [CrashKind; 3] = [CrashKind::Panic, CrashKind::TaskStalled, CrashKind::WatchdogTimeout];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CrashKind::Panic => "panic",
+            CrashKind::TaskStalled => "task stalled",
+            CrashKind::WatchdogTimeout => "watchdog timeout",
+        }
+    }
+}
+
+/// Cause of a crash
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CrashLog {
+    pub kind: CrashKind,
+    pub message: heapless::String<MAX_MESSAGE>,
+}
+
+impl CrashLog {
+    pub fn new(kind: CrashKind) -> Self {
+        Self {
+            kind,
+            message: heapless::String::new(),
+        }
+    }
+
+    /// Encode crash log, for storage in RAM or flash
+    pub fn encode(&self) -> [u8; ENCODED_SIZE] {
+        let mut buf = [0xFF; ENCODED_SIZE];
+        buf[..4].copy_from_slice(&MAGIC);
+        buf[4] = CrashKind::ALL.iter().position(|k| *k == self.kind).unwrap_or(0) as u8;
+        buf[5] = self.message.len() as u8;
+        buf[MESSAGE_OFFSET..MESSAGE_OFFSET + self.message.len()].copy_from_slice(self.message.as_bytes());
+        let checksum = checksum(&buf[..ENCODED_SIZE - 2]);
+        buf[ENCODED_SIZE - 2..].copy_from_slice(&checksum.to_le_bytes());
+        buf
+    }
+
+    /// Decode a crash log previously encoded with [`CrashLog::encode`]
+    ///
+    /// Returns `None` if the data does not contain a valid crash log (e.g. erased flash, or uninitialized RAM).
+    pub fn decode(buf: &[u8; ENCODED_SIZE]) -> Option<Self> {
+        let checksum = checksum(&buf[..ENCODED_SIZE - 2]).to_le_bytes();
+        if buf[..4] != MAGIC || buf[ENCODED_SIZE - 2..] != checksum {
+            return None;
+        }
+        let kind = *CrashKind::ALL.get(buf[4] as usize)?;
+        let len = buf[5] as usize;
+        if len > MAX_MESSAGE {
+            return None;
+        }
+        let message = core::str::from_utf8(&buf[MESSAGE_OFFSET..MESSAGE_OFFSET + len]).ok()?;
+        Some(Self {
+            kind,
+            message: message.try_into().ok()?,
+        })
+    }
+}
+
+impl Write for CrashLog {
+    /// Appends to the message (on a single line), truncating what does not fit
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars().map(|c| if c == '\n' { ' ' } else { c }) {
+            if self.message.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0xFFFFu16, |sum, byte| sum.rotate_left(5) ^ *byte as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replace the checksum of an encoded record, after modifying it
+    fn fix_checksum(buf: &mut [u8; ENCODED_SIZE]) {
+        let checksum = checksum(&buf[..ENCODED_SIZE - 2]);
+        buf[ENCODED_SIZE - 2..].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        const TASK: &str = "net_manager";
+        let mut log = CrashLog::new(CrashKind::TaskStalled);
+        _ = write!(log, "leds, {}", TASK);
+        assert_eq!(CrashLog::decode(&log.encode()), Some(log));
+        for kind in CrashKind::ALL {
+            let log = CrashLog::new(kind);
+            assert_eq!(CrashLog::decode(&log.encode()), Some(log));
+        }
+        let mut log = CrashLog::new(CrashKind::Panic);
+        _ = log.write_str(&"x".repeat(MAX_MESSAGE));
+        assert_eq!(CrashLog::decode(&log.encode()), Some(log));
+    }
+
+    #[test]
+    fn test_reject_garbage() {
+        assert_eq!(CrashLog::decode(&[0xFF; ENCODED_SIZE]), None);
+        assert_eq!(CrashLog::decode(&[0; ENCODED_SIZE]), None);
+        let mut buf = [0; ENCODED_SIZE];
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = (i * 151 + 7) as u8;
+        }
+        assert_eq!(CrashLog::decode(&buf), None);
+
+        // a single flipped bit
+        let mut log = CrashLog::new(CrashKind::Panic);
+        _ = write!(log, "oops");
+        let mut buf = log.encode();
+        buf[7] ^= 0x04;
+        assert_eq!(CrashLog::decode(&buf), None);
+
+        // valid checksum, but not UTF-8
+        let mut buf = log.encode();
+        buf[MESSAGE_OFFSET] = 0xFF;
+        fix_checksum(&mut buf);
+        assert_eq!(CrashLog::decode(&buf), None);
+    }
+
+    #[test]
+    fn test_reject_out_of_range() {
+        let log = CrashLog::new(CrashKind::WatchdogTimeout);
+        let mut buf = log.encode();
+        buf[4] = CrashKind::ALL.len() as u8;
+        fix_checksum(&mut buf);
+        assert_eq!(CrashLog::decode(&buf), None);
+
+        for len in [MAX_MESSAGE + 1, ENCODED_SIZE, 0xFF] {
+            let mut buf = log.encode();
+            buf[MESSAGE_OFFSET..ENCODED_SIZE - 2].fill(b'x');
+            buf[5] = len as u8;
+            fix_checksum(&mut buf);
+            assert_eq!(CrashLog::decode(&buf), None, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_truncate_message() {
+        let mut log = CrashLog::new(CrashKind::Panic);
+        _ = write!(log, "panicked at src/main.rs:12:5:\n{}", "long message ".repeat(20));
+        assert_eq!(log.message.len(), MAX_MESSAGE);
+        assert!(log.message.starts_with("panicked at src/main.rs:12:5: long message"));
+        // further writes are dropped
+        _ = write!(log, "more");
+        assert_eq!(log.message.len(), MAX_MESSAGE);
+        assert!(!log.message.ends_with("more"));
+        assert_eq!(CrashLog::decode(&log.encode()), Some(log));
+    }
+}
@@ -0,0 +1,125 @@
+//! Current measurement, between the `ISENSE_PLUS` and `ISENSE_MINUS` nodes
+//!
+//! The current flows through a shunt resistor, and is measured by an INA219 (on I2C).
+
+use embedded_hal_async::i2c::I2c;
+
+use crate::{write_voltage, Millivolts};
+
+/// Current, in microamps
+pub type Microamps = i32;
+
+/// Voltage, in microvolts
+pub type Microvolts = i32;
+
+/// Nominal resistance of the shunt resistor between `ISENSE_PLUS` and `ISENSE_MINUS` (in milliohms)
+pub const SHUNT_RESISTANCE: i32 = 2000;
+
+/// Factory default I2C address of the INA219 (A0 and A1 tied to GND)
+pub const INA219_ADDRESS: u8 = 0x40;
+
+const REG_CONFIG: u8 = 0x00;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+
+/// 16V bus voltage range (BRNG = 0), ±320mV shunt voltage range (PG = 0b11),
+/// 12-bit resolution (BADC, SADC = 0b0011), continuous conversion of both voltages (MODE = 0b111)
+const CONFIG: u16 = 0x199F;
+
+/// A single measurement
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CurrentReading {
+    /// Voltage on `ISENSE_MINUS`, relative to GND
+    pub bus_voltage: Millivolts,
+    /// Voltage across the shunt resistor
+    pub shunt_voltage: Microvolts,
+    /// Current flowing from `ISENSE_PLUS` to `ISENSE_MINUS`
+    pub current: Microamps,
+}
+
+impl core::fmt::Display for CurrentReading {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "current ")?;
+        write_current(f, self.current)?;
+        write!(f, ", bus voltage ")?;
+        write_voltage(f, self.bus_voltage)
+    }
+}
+
+/// Writes a current in milliamps, with three decimals (like `-12.345mA`)
+pub fn write_current<W: core::fmt::Write>(w: &mut W, current: Microamps) -> core::fmt::Result {
+    let sign = if current < 0 { "-" } else { "" };
+    let abs = current.unsigned_abs();
+    write!(w, "{}{}.{:03}mA", sign, abs / 1000, abs % 1000)
+}
+
+/// Driver for the INA219 current sensor
+///
+/// The current is derived from the shunt voltage (instead of using the calibration and current registers
+/// of the INA219), so the full resolution of the shunt voltage is retained.
+pub struct Ina219<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Ina219<I> {
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Configure the sensor for continuous measurements. Must be called before the first [`Ina219::read`].
+    pub async fn init(&mut self) -> Result<(), I::Error> {
+        let [high, low] = CONFIG.to_be_bytes();
+        self.i2c.write(self.address, &[REG_CONFIG, high, low]).await
+    }
+
+    async fn read_register(&mut self, register: u8) -> Result<u16, I::Error> {
+        let mut buf = [0; 2];
+        self.i2c.write_read(self.address, &[register], &mut buf).await?;
+        Ok(u16::from_be_bytes(buf))
+    }
+
+    /// Read the latest measurement
+    pub async fn read(&mut self) -> Result<CurrentReading, I::Error> {
+        // LSB is 10µV
+        let shunt_voltage = self.read_register(REG_SHUNT_VOLTAGE).await? as i16 as Microvolts * 10;
+        // upper 13 bits, LSB is 4mV
+        let bus_voltage = (self.read_register(REG_BUS_VOLTAGE).await? >> 3) as Millivolts * 4;
+        Ok(CurrentReading {
+            bus_voltage,
+            shunt_voltage,
+            current: shunt_voltage * 1000 / SHUNT_RESISTANCE,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    #[test]
+    fn test_ina219() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(INA219_ADDRESS, vec![0x00, 0x19, 0x9F]),
+            // -12.34mV
+            Transaction::write_read(INA219_ADDRESS, vec![0x01], (-1234i16).to_be_bytes().to_vec()),
+            // 5V, with the "conversion ready" bit set
+            Transaction::write_read(INA219_ADDRESS, vec![0x02], ((1250u16 << 3) | 0b10).to_be_bytes().to_vec()),
+        ]);
+        let mut sensor = Ina219::new(i2c.clone(), INA219_ADDRESS);
+        block_on(sensor.init()).unwrap();
+        let reading = block_on(sensor.read()).unwrap();
+        assert_eq!(
+            reading,
+            CurrentReading {
+                bus_voltage: 5000,
+                shunt_voltage: -12340,
+                current: -6170,
+            }
+        );
+        assert_eq!(format!("{}", reading), "current -6.170mA, bus voltage 5.00V");
+        i2c.done();
+    }
+}
@@ -0,0 +1,314 @@
+//! Programmable voltage outputs, connected to the `DAC0` and `DAC1` nodes
+//!
+//! Contains a driver for the MCP4728 (quad 12-bit DAC, on I2C) which generates the voltages,
+//! and the waveforms which can be played on the outputs.
+
+use embedded_hal_async::i2c::I2c;
+
+use crate::{board::Node, rails::parse_milli, Millivolts};
+
+/// One of the DAC outputs
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DacChannel {
+    Dac0,
+    Dac1,
+}
+
+impl DacChannel {
+    pub const ALL: [DacChannel; 2] = [DacChannel::Dac0, DacChannel::Dac1];
+
+    /// Parses the channel number (`0` or `1`)
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            "0" => Some(DacChannel::Dac0),
+            "1" => Some(DacChannel::Dac1),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Node which carries the output voltage
+    pub fn node(&self) -> Node {
+        match self {
+            DacChannel::Dac0 => Node::DAC0,
+            DacChannel::Dac1 => Node::DAC1,
+        }
+    }
+
+    /// Channel of the MCP4728 which generates the output
+    fn mcp4728_channel(&self) -> u8 {
+        match self {
+            DacChannel::Dac0 => 0,
+            DacChannel::Dac1 => 1,
+        }
+    }
+
+    /// Nominal voltages at the lowest and highest DAC code
+    ///
+    /// The outputs are amplified (and shifted) by op-amps, so they can reach negative voltages.
+    /// These are the values the circuit is designed for, individual boards are not calibrated (yet).
+    pub fn calibration(&self) -> Calibration {
+        #[cfg(feature = "board-v4")]
+        {
+            match self {
+                DacChannel::Dac0 => Calibration { zero: 0, full_scale: 5000 },
+                DacChannel::Dac1 => Calibration { zero: -8000, full_scale: 8000 },
+            }
+        }
+        #[cfg(feature = "board-v5")]
+        {
+            Calibration { zero: -8000, full_scale: 8000 }
+        }
+    }
+}
+
+/// Linear mapping between voltages and 12-bit codes (of the DAC, or the ADC)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Calibration {
+    /// Voltage at code 0
+    pub zero: Millivolts,
+    /// Voltage at code [`MAX_CODE`]
+    pub full_scale: Millivolts,
+}
+
+/// Highest code of the 12-bit DAC
+pub const MAX_CODE: u16 = 4095;
+
+impl Calibration {
+    /// Whether the output can produce the given voltage
+    pub fn contains(&self, voltage: Millivolts) -> bool {
+        (self.zero.min(self.full_scale)..=self.zero.max(self.full_scale)).contains(&voltage)
+    }
+
+    /// DAC code for the given voltage, rounded to the closest one. Voltages out of range are clamped.
+    pub fn code(&self, voltage: Millivolts) -> u16 {
+        let span = (self.full_scale - self.zero) as i64;
+        let offset = (voltage - self.zero) as i64 * MAX_CODE as i64;
+        let code = (2 * offset + span.signum() * span) / (2 * span);
+        code.clamp(0, MAX_CODE as i64) as u16
+    }
+
+    /// Voltage for the given code
+    pub fn voltage(&self, code: u16) -> Millivolts {
+        let span = (self.full_scale - self.zero) as i64;
+        self.zero + (span * code.min(MAX_CODE) as i64 / MAX_CODE as i64) as Millivolts
+    }
+}
+
+/// Factory default I2C address of the MCP4728
+pub const MCP4728_ADDRESS: u8 = 0x60;
+
+/// Driver for the MCP4728 quad DAC
+///
+/// Outputs use VDD as reference, and are updated as soon as they are written.
+pub struct Mcp4728<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Mcp4728<I> {
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Set the code of a single channel ("multi-write" command)
+    pub async fn write_code(&mut self, channel: u8, code: u16) -> Result<(), I::Error> {
+        let code = code.min(MAX_CODE);
+        // command 0b01000, channel select, UDAC = 0 (update immediately)
+        let command = 0x40 | (channel & 0b11) << 1;
+        // VREF = 0 (VDD), power-down = 0b00 (normal), gain = 0, upper 4 bits of the code
+        let upper = (code >> 8) as u8;
+        self.i2c.write(self.address, &[command, upper, code as u8]).await
+    }
+
+    /// Set the voltage of a DAC output
+    pub async fn set_voltage(&mut self, channel: DacChannel, voltage: Millivolts) -> Result<(), I::Error> {
+        self.write_code(channel.mcp4728_channel(), channel.calibration().code(voltage)).await
+    }
+}
+
+/// What a DAC output should produce
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DacOutput {
+    /// A constant voltage
+    Voltage(Millivolts),
+    /// A periodic signal
+    Wave(Wave),
+}
+
+/// Shape of a [`Wave`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 3] = [Waveform::Sine, Waveform::Square, Waveform::Triangle];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Square => "square",
+            Waveform::Triangle => "triangle",
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|waveform| waveform.label() == input)
+    }
+}
+
+/// Frequency, in millihertz
+pub type Millihertz = u32;
+
+/// Highest frequency of a [`Wave`]. Waves are sampled at 1kHz, so this leaves 10 samples per period.
+pub const MAX_FREQUENCY: Millihertz = 100_000;
+
+/// Parses a frequency in hertz (like `50` or `0.5`), with up to three decimals
+///
+/// Returns `None` unless the frequency is above zero, and at most [`MAX_FREQUENCY`].
+pub fn parse_frequency(input: &str) -> Option<Millihertz> {
+    let frequency = parse_milli(input.strip_suffix("Hz").unwrap_or(input))?;
+    (1..=MAX_FREQUENCY as i32)
+        .contains(&frequency)
+        .then_some(frequency as Millihertz)
+}
+
+/// A periodic signal, swinging between two voltages
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Wave {
+    pub waveform: Waveform,
+    pub frequency: Millihertz,
+    pub low: Millivolts,
+    pub high: Millivolts,
+}
+
+/// Position within the period of a [`Wave`], with a full period spanning the whole range of `u32`
+pub type Phase = u32;
+
+impl Wave {
+    /// Phase increment per sample, for the given sample rate (in Hz)
+    pub fn phase_step(&self, sample_rate: u32) -> Phase {
+        (((self.frequency as u64) << 32) / (sample_rate as u64 * 1000)) as Phase
+    }
+
+    /// Voltage at the given phase
+    ///
+    /// All waveforms start at their midpoint (or, for square waves, the high level) and rise first.
+    pub fn sample(&self, phase: Phase) -> Millivolts {
+        let span = (self.high - self.low) as i64;
+        // position within the half period, from 0 to HALF
+        const HALF: i64 = 1 << 15;
+        let x = (phase >> 16) as i64 % HALF;
+        let rising = phase < 1 << 31;
+        // level between 0 (low) and 1 (high), as a fraction
+        let (num, den) = match self.waveform {
+            Waveform::Square => (rising as i64, 1),
+            Waveform::Triangle => {
+                // starts at the midpoint, peaks at a quarter of the period
+                let t = (phase.wrapping_add(1 << 30) >> 16) as i64;
+                let t = if t < HALF { t } else { 2 * HALF - t };
+                (t, HALF)
+            }
+            Waveform::Sine => {
+                // Bhaskara I's approximation of sin(x) over a half period (max. error ~0.2%)
+                let p = x * (HALF - x);
+                let (num, den) = (16 * p, 5 * HALF * HALF - 4 * p);
+                let sign = if rising { 1 } else { -1 };
+                (den + sign * num, 2 * den)
+            }
+        };
+        self.low + (span * num / den) as Millivolts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    #[test]
+    fn test_calibration() {
+        let calibration = Calibration { zero: -8000, full_scale: 8000 };
+        assert_eq!(calibration.code(-8000), 0);
+        assert_eq!(calibration.code(0), 2048);
+        assert_eq!(calibration.code(8000), MAX_CODE);
+        assert_eq!(calibration.code(9000), MAX_CODE);
+        assert!(calibration.contains(-8000));
+        assert!(!calibration.contains(8001));
+        let calibration = Calibration { zero: 0, full_scale: 5000 };
+        assert_eq!(calibration.code(3300), 2703);
+        assert_eq!(calibration.code(-1), 0);
+        assert_eq!(calibration.voltage(2703), 3300);
+        assert_eq!(calibration.voltage(MAX_CODE), 5000);
+    }
+
+    #[test]
+    fn test_mcp4728() {
+        let mut i2c = Mock::new(&[
+            Transaction::write(MCP4728_ADDRESS, vec![0x40, 0x0F, 0xFF]),
+            Transaction::write(MCP4728_ADDRESS, vec![0x42, 0x08, 0x00]),
+        ]);
+        let mut dac = Mcp4728::new(i2c.clone(), MCP4728_ADDRESS);
+        block_on(dac.write_code(0, MAX_CODE)).unwrap();
+        block_on(dac.write_code(1, 2048)).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_set_voltage() {
+        let calibration = DacChannel::Dac1.calibration();
+        let code = calibration.code(calibration.full_scale);
+        let mut i2c = Mock::new(&[Transaction::write(
+            MCP4728_ADDRESS,
+            vec![0x42, (code >> 8) as u8, code as u8],
+        )]);
+        let mut dac = Mcp4728::new(i2c.clone(), MCP4728_ADDRESS);
+        block_on(dac.set_voltage(DacChannel::Dac1, calibration.full_scale)).unwrap();
+        i2c.done();
+    }
+
+    #[test]
+    fn test_parse_frequency() {
+        assert_eq!(parse_frequency("50"), Some(50_000));
+        assert_eq!(parse_frequency("0.5Hz"), Some(500));
+        assert_eq!(parse_frequency("0"), None);
+        assert_eq!(parse_frequency("100.001"), None);
+        assert_eq!(Waveform::parse("triangle"), Some(Waveform::Triangle));
+        assert_eq!(Waveform::parse("sawtooth"), None);
+    }
+
+    #[test]
+    fn test_phase_step() {
+        let wave = Wave { waveform: Waveform::Square, frequency: 250_000, low: 0, high: 1000 };
+        // four samples per period
+        assert_eq!(wave.phase_step(1000), 1 << 30);
+        let wave = Wave { frequency: 1000, ..wave };
+        assert_eq!(wave.phase_step(1000) as u64 * 1000, (1 << 32) - 296);
+    }
+
+    #[test]
+    fn test_samples() {
+        let quarter = 1 << 30;
+        let samples = |waveform| {
+            let wave = Wave { waveform, frequency: 1000, low: -1000, high: 3000 };
+            [0, quarter, 2 * quarter, 3 * quarter].map(|phase| wave.sample(phase))
+        };
+        assert_eq!(samples(Waveform::Sine), [1000, 3000, 1000, -1000]);
+        assert_eq!(samples(Waveform::Square), [3000, 3000, -1000, -1000]);
+        assert_eq!(samples(Waveform::Triangle), [1000, 3000, 1000, -1000]);
+
+        let wave = Wave { waveform: Waveform::Sine, frequency: 1000, low: -1000, high: 1000 };
+        for degrees in [10, 30, 45, 60, 80, 200, 300] {
+            let phase = ((degrees as u64) << 32) / 360;
+            let expected = (degrees as f64).to_radians().sin() * 1000.0;
+            assert!((wave.sample(phase as Phase) as f64 - expected).abs() < 3.0, "{}°", degrees);
+        }
+    }
+}
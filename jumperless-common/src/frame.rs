@@ -0,0 +1,274 @@
+//! Colors of all LEDs on the board ("frames")
+//!
+//! Contains the logic to derive colors from the nets, as well as the "dump" format used to transfer a frame
+//! to the host, and (with the `std` feature) renderers which draw a dumped frame in the layout of the board.
+
+use core::fmt::Write;
+
+use crate::{
+    board,
+    board::Node,
+    color::{voltage_color, Color},
+    types::Net,
+    Rail, Rails,
+};
+
+/// Color of unused nano header LEDs ("headerglow")
+const HEADERGLOW: Color = (0x02, 0x00, 0x08);
+
+/// Calls `set` for LEDs which reflect the given nets, with their color
+///
+/// In detail, this:
+/// - lights up the LEDs of all nodes belonging to each net. Nets connected to a rail (if the rails are nodes)
+///   get a color representing the rail voltage (see [`voltage_color`]).
+/// - lights up the rails which are not nodes, in colors representing their voltage
+/// - adds headerglow to the unused nano LEDs
+///
+/// `colors` holds the color of each net in `nets`, at the same index.
+/// LEDs which are not mentioned should be turned off.
+pub fn render_nets(nets: &[Net<Node>], colors: &[Color], rails: &Rails, mut set: impl FnMut(usize, Color)) {
+    for &i in board::leds::HEADER {
+        set(i, HEADERGLOW);
+    }
+
+    for (net, color) in nets.iter().zip(colors) {
+        let color = Rail::ALL
+            .into_iter()
+            .find(|rail| rail.node().is_some_and(|node| net.nodes.contains(node)))
+            .map(|rail| voltage_color(rails.get(rail)))
+            .unwrap_or(*color);
+        for node in net.nodes.iter() {
+            for &i in node.leds() {
+                set(i, color);
+            }
+        }
+    }
+
+    for rail in Rail::ALL {
+        let color = voltage_color(rails.get(rail));
+        for &i in rail.leds() {
+            set(i, color);
+        }
+    }
+}
+
+/// Writes the color of a single LED, in dump format
+///
+/// A dump is a single line, holding the color of each LED (in order of their index) as `rrggbb`,
+/// separated by spaces. This writes the separator too, for every LED but the first one.
+pub fn write_dump_entry<W: Write>(w: &mut W, index: usize, (r, g, b): Color) -> core::fmt::Result {
+    if index > 0 {
+        w.write_char(' ')?;
+    }
+    write!(w, "{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(feature = "std")]
+pub use host::*;
+
+#[cfg(feature = "std")]
+mod host {
+    use super::*;
+    use crate::color::parse_hex;
+
+    /// Parse a dump (see [`write_dump_entry`])
+    ///
+    /// Returns `None` if any of the colors is invalid.
+    pub fn parse_dump(input: &str) -> Option<Vec<Color>> {
+        input.split_ascii_whitespace().map(parse_hex).collect()
+    }
+
+    /// A line of LEDs, as drawn by the renderers
+    pub struct Line {
+        pub label: String,
+        /// LED indices, `None` leaving a gap
+        pub leds: Vec<Option<usize>>,
+    }
+
+    /// Arrangement of the LEDs for drawing, roughly resembling the board
+    ///
+    /// Breadboard rows are drawn as columns, with the top half above the bottom half. LEDs which
+    /// do not belong to any of the known parts of the board end up in a line labeled "other".
+    pub fn layout() -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut placed = [false; board::leds::COUNT];
+        let mut add_line = |lines: &mut Vec<Line>, label: String, leds: Vec<Option<usize>>| {
+            for &i in leds.iter().flatten() {
+                placed[i] = true;
+            }
+            lines.push(Line { label, leds });
+        };
+
+        let rail = |rail: Rail| -> Vec<Option<usize>> {
+            let node_leds = rail.node().map(|node| node.leds()).unwrap_or(&[]);
+            node_leds.iter().chain(rail.leds()).copied().map(Some).collect()
+        };
+        add_line(&mut lines, "top rail".into(), rail(Rail::Top));
+
+        for rows in [1..=30, 31..=60] {
+            let nodes: Vec<Node> = rows
+                .map(|row| row.to_string().parse().expect("breadboard row"))
+                .collect();
+            let height = nodes.iter().map(|node| node.leds().len()).max().unwrap_or(0);
+            for k in 0..height {
+                let label = if k == 0 {
+                    format!("{}-{}", nodes[0].as_str(), nodes[nodes.len() - 1].as_str())
+                } else {
+                    String::new()
+                };
+                let leds = nodes.iter().map(|node| node.leds().get(k).copied()).collect();
+                add_line(&mut lines, label, leds);
+            }
+        }
+
+        add_line(&mut lines, "bottom rail".into(), rail(Rail::Bottom));
+        add_line(
+            &mut lines,
+            "header".into(),
+            board::leds::HEADER.iter().copied().map(Some).collect(),
+        );
+        add_line(
+            &mut lines,
+            "logo".into(),
+            board::leds::LOGO.iter().copied().map(Some).collect(),
+        );
+
+        let other: Vec<Option<usize>> = (0..board::leds::COUNT)
+            .filter(|i| !placed[*i])
+            .map(Some)
+            .collect();
+        if !other.is_empty() {
+            lines.push(Line { label: "other".into(), leds: other });
+        }
+        lines
+    }
+
+    /// LED colors are quite dark, so they are brightened for display, keeping their hue
+    fn display_color((r, g, b): Color) -> Color {
+        let max = r.max(g).max(b) as u32;
+        if max == 0 {
+            return (0, 0, 0);
+        }
+        let scale = |c: u8| (c as u32 * 255 / max) as u8;
+        (scale(r), scale(g), scale(b))
+    }
+
+    const LABEL_WIDTH: usize = 12;
+
+    /// Draw the frame using ANSI (true color) escape sequences
+    ///
+    /// LEDs which are off are drawn as dots. LEDs missing from `colors` are left blank.
+    pub fn render_terminal(colors: &[Color]) -> String {
+        let mut out = String::new();
+        for line in layout() {
+            _ = write!(out, "{:>width$} ", line.label, width = LABEL_WIDTH);
+            for i in line.leds {
+                match i.and_then(|i| colors.get(i)) {
+                    Some((0, 0, 0)) => out.push_str(" ·"),
+                    Some(&color) => {
+                        let (r, g, b) = display_color(color);
+                        _ = write!(out, "\x1b[38;2;{};{};{}m █\x1b[0m", r, g, b);
+                    }
+                    None => out.push_str("  "),
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draw the frame as an SVG image
+    pub fn render_svg(colors: &[Color]) -> String {
+        const SPACING: usize = 12;
+        const LABEL: usize = 90;
+        let lines = layout();
+        let width = LABEL + SPACING * lines.iter().map(|line| line.leds.len()).max().unwrap_or(0);
+        let height = SPACING * lines.len();
+        let mut out = String::new();
+        _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="monospace" font-size="10">"#
+        );
+        _ = writeln!(out, r#"<rect width="{width}" height="{height}" fill="black"/>"#);
+        for (y, line) in lines.iter().enumerate() {
+            let cy = y * SPACING + SPACING / 2;
+            _ = writeln!(
+                out,
+                r#"<text x="2" y="{}" fill="gray">{}</text>"#,
+                cy + 3,
+                line.label
+            );
+            for (x, i) in line.leds.iter().enumerate() {
+                if let Some(&color) = i.and_then(|i| colors.get(i)) {
+                    let (r, g, b) = display_color(color);
+                    _ = writeln!(
+                        out,
+                        r##"<circle cx="{}" cy="{cy}" r="{}" fill="#{r:02x}{g:02x}{b:02x}"/>"##,
+                        LABEL + x * SPACING + SPACING / 2,
+                        SPACING * 2 / 5,
+                    );
+                }
+            }
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(nets: &[Net<Node>], colors: &[Color]) -> Vec<Color> {
+        let mut frame = vec![(0, 0, 0); board::leds::COUNT];
+        let rails = Rails { top: 5000, bottom: 5000 };
+        render_nets(nets, colors, &rails, |i, color| frame[i] = color);
+        frame
+    }
+
+    #[test]
+    fn test_render_nets() {
+        let red = (0xFF, 0x00, 0x00);
+        let nets = [Net::from_iter(1.into(), [Node::_3, Node::_40].into_iter())];
+        let frame = render(&nets, &[red]);
+        for i in Node::_3.leds().iter().chain(Node::_40.leds()) {
+            assert_eq!(frame[*i], red);
+        }
+        for i in Node::_4.leds() {
+            assert_eq!(frame[*i], (0, 0, 0));
+        }
+        for i in Rail::Top.leds() {
+            assert_eq!(frame[*i], voltage_color(5000));
+        }
+    }
+
+    #[test]
+    fn test_dump() {
+        let nets = [Net::from_iter(1.into(), [Node::_7].into_iter())];
+        let frame = render(&nets, &[(0x12, 0xAB, 0x00)]);
+        let mut dump = String::new();
+        for (i, color) in frame.iter().enumerate() {
+            write_dump_entry(&mut dump, i, *color).unwrap();
+        }
+        assert_eq!(parse_dump(&dump), Some(frame));
+        assert_eq!(parse_dump("ff0000 12345"), None);
+    }
+
+    #[test]
+    fn test_layout() {
+        // every LED is drawn exactly once
+        let mut count = vec![0; board::leds::COUNT];
+        for line in layout() {
+            for i in line.leds.into_iter().flatten() {
+                count[i] += 1;
+            }
+        }
+        assert!(count.iter().all(|c| *c == 1));
+        // boards without an LED mapping have nothing to draw
+        if board::leds::COUNT > 0 {
+            let colors = vec![(0, 0, 0x10); board::leds::COUNT];
+            assert!(render_svg(&colors).contains(r##"fill="#0000ff""##));
+            assert!(render_terminal(&colors).contains("\x1b[38;2;0;0;255m"));
+        }
+    }
+}
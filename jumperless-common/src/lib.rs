@@ -10,9 +10,29 @@ pub mod board;
 mod chip_status;
 pub use chip_status::ChipStatus;
 
-mod nets_to_connections;
+pub mod nets_to_connections;
 pub use nets_to_connections::nets_to_connections;
 
+pub mod color;
+
+pub mod frame;
+
+pub mod brightness;
+
+pub mod dac;
+
+pub mod current_sense;
+
+pub mod probe;
+
+pub mod crash_log;
+
+mod supply_switch_pos;
+pub use supply_switch_pos::SupplySwitchPos;
+
+mod rails;
+pub use rails::{parse_voltage, write_voltage, Millivolts, Rail, Rails};
+
 /// A single crosspoint coordinate, with associated NetId.
 ///
 /// Represents a unique switch (by Chip, X and Y coordinate) on the board.
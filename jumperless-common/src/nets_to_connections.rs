@@ -12,11 +12,31 @@ use heapless::Vec;
 const MAX_NETS: usize = 60;
 
 #[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Copy, Clone)]
 pub enum Error {
     MissingPort(NetId, Edge),
     MissingLane(NetId, Edge, Edge),
 }
 
+impl Error {
+    /// The net which could not be routed
+    pub fn net_id(&self) -> NetId {
+        match self {
+            Error::MissingPort(net_id, _) => *net_id,
+            Error::MissingLane(net_id, _, _) => *net_id,
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::MissingPort(net_id, _) => write!(f, "no free port left to connect net {net_id}"),
+            Error::MissingLane(net_id, _, _) => write!(f, "no free lane left to connect net {net_id}"),
+        }
+    }
+}
+
 /// Turn given list of `nets` into connections. The connections are made by modifying the given `chip_status` (which is expected to be empty to begin with).
 ///
 /// The board is used to map nodes to ports and to locate lanes between chips.
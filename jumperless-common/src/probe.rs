@@ -0,0 +1,209 @@
+//! Voltage measurement of arbitrary nodes
+//!
+//! A node is measured by temporarily connecting it to one of the `ADC0`-`ADC3` nodes, which lead to
+//! the ADC inputs of the RP2040. The probe is routed on top of the existing connections, without changing
+//! any of them, so it can be connected and disconnected again by only toggling the additional crosspoints.
+
+use jumperless_types::{Net, NetId};
+
+use crate::{
+    board::{Board, Node},
+    dac::Calibration,
+    nets_to_connections::{self, nets_to_connections},
+    ChipStatus, Crosspoint, CrosspointConfig, Millivolts,
+};
+
+/// One of the ADC inputs
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AdcChannel {
+    Adc0,
+    Adc1,
+    Adc2,
+    Adc3,
+}
+
+impl AdcChannel {
+    pub const ALL: [AdcChannel; 4] = [AdcChannel::Adc0, AdcChannel::Adc1, AdcChannel::Adc2, AdcChannel::Adc3];
+
+    /// Index of the channel, which matches the RP2040's ADC channel (on GPIO 26 to 29)
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn node(&self) -> Node {
+        match self {
+            AdcChannel::Adc0 => Node::ADC0,
+            AdcChannel::Adc1 => Node::ADC1,
+            AdcChannel::Adc2 => Node::ADC2,
+            AdcChannel::Adc3 => Node::ADC3,
+        }
+    }
+
+    /// Nominal voltages at the node, for the lowest and highest ADC reading
+    ///
+    /// The inputs are scaled (and shifted) to the ADC's range. Like for the DAC, individual boards are not calibrated.
+    pub fn calibration(&self) -> Calibration {
+        #[cfg(feature = "board-v4")]
+        {
+            match self {
+                AdcChannel::Adc3 => Calibration { zero: -8000, full_scale: 8000 },
+                _ => Calibration { zero: 0, full_scale: 5000 },
+            }
+        }
+        #[cfg(feature = "board-v5")]
+        {
+            Calibration { zero: -8000, full_scale: 8000 }
+        }
+    }
+
+    /// Voltage at the node, for the given (12-bit) reading
+    pub fn voltage(&self, reading: u16) -> Millivolts {
+        self.calibration().voltage(reading)
+    }
+}
+
+/// Reasons why a node cannot be probed
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ProbeError {
+    /// All ADC nodes are already part of a net (or the node to probe is one of them)
+    NoFreeAdc,
+    /// The probe could not be routed
+    Routing(nets_to_connections::Error),
+    /// The probe could only be routed by changing existing connections
+    WouldDisturb,
+}
+
+impl core::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProbeError::NoFreeAdc => write!(f, "no free ADC left"),
+            ProbeError::Routing(err) => write!(f, "{}", err),
+            ProbeError::WouldDisturb => write!(f, "probe would disturb existing connections"),
+        }
+    }
+}
+
+/// Route a probe from `node` to a free ADC, on top of the `current` connections (which were computed from `nets`)
+///
+/// ADCs with a wider voltage range are preferred. If `node` is part of a net, the whole net is probed.
+///
+/// Returns the chosen ADC, and the connections including the probe. These contain all of the `current`
+/// connections, see [`added_crosspoints`].
+pub fn route_probe(
+    nets: &[Net<Node>],
+    current: &ChipStatus,
+    node: Node,
+    board: &Board,
+) -> Result<(AdcChannel, ChipStatus), ProbeError> {
+    let mut candidates = AdcChannel::ALL;
+    candidates.sort_unstable_by_key(|adc| {
+        let calibration = adc.calibration();
+        (-(calibration.full_scale - calibration.zero).abs(), adc.index())
+    });
+    let used = |node: Node| nets.iter().any(|net| net.nodes.contains(node));
+
+    let mut result = Err(ProbeError::NoFreeAdc);
+    for adc in candidates {
+        if adc.node() == node || used(adc.node()) {
+            continue;
+        }
+        let (probe_net, replaced) = match nets.iter().find(|net| net.nodes.contains(node)) {
+            Some(net) => {
+                let mut probe_net = net.clone();
+                probe_net.nodes.insert(adc.node());
+                (probe_net, Some(net.id))
+            }
+            None => {
+                let id = nets.iter().map(|net| net.id.index() + 1).max().unwrap_or(0) + 1;
+                let probe_net = Net::from_iter(NetId::from(id as u8), [node, adc.node()].into_iter());
+                (probe_net, None)
+            }
+        };
+        let probe_nets = nets
+            .iter()
+            .map(|net| if Some(net.id) == replaced { &probe_net } else { net })
+            .chain(replaced.is_none().then_some(&probe_net));
+
+        let mut status = ChipStatus::default();
+        result = match nets_to_connections(probe_nets, &mut status, board) {
+            Ok(()) => {
+                let config: CrosspointConfig = status.crosspoints().collect();
+                if current.crosspoints().all(|crosspoint| config.get(crosspoint)) {
+                    return Ok((adc, status));
+                }
+                Err(ProbeError::WouldDisturb)
+            }
+            Err(err) => Err(ProbeError::Routing(err)),
+        };
+    }
+    result
+}
+
+/// Crosspoints which `probe` (see [`route_probe`]) adds to the `current` connections
+pub fn added_crosspoints<'a>(current: &ChipStatus, probe: &'a ChipStatus) -> impl Iterator<Item = Crosspoint> + 'a {
+    let current: CrosspointConfig = current.crosspoints().collect();
+    probe.crosspoints().filter(move |crosspoint| !current.get(*crosspoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::init_board;
+
+    fn nets() -> Vec<Net<Node>> {
+        vec![
+            Net::from_iter(1.into(), [Node::GND].into_iter()),
+            Net::from_iter(8.into(), [Node::_3, Node::_40].into_iter()),
+            Net::from_iter(9.into(), [Node::_10, Node::_11].into_iter()),
+        ]
+    }
+
+    fn route(nets: &[Net<Node>]) -> ChipStatus {
+        let mut status = ChipStatus::default();
+        nets_to_connections(nets.iter(), &mut status, &init_board()).unwrap();
+        status
+    }
+
+    #[test]
+    fn test_probe_existing_net() {
+        let nets = nets();
+        let current = route(&nets);
+        let (adc, probe) = route_probe(&nets, &current, Node::_40, &init_board()).unwrap();
+        let added: Vec<Crosspoint> = added_crosspoints(&current, &probe).collect();
+        assert!(!added.is_empty());
+        // the probe joins the net of the node
+        assert!(added.iter().all(|crosspoint| crosspoint.net_id == 8.into()));
+        // existing connections stay in place
+        let config: CrosspointConfig = probe.crosspoints().collect();
+        assert!(current.crosspoints().all(|crosspoint| config.get(crosspoint)));
+        assert_eq!(probe.crosspoints().count(), current.crosspoints().count() + added.len());
+        let calibration = adc.calibration();
+        assert_eq!(adc.voltage(0), calibration.zero);
+        assert_eq!(adc.voltage(4095), calibration.full_scale);
+    }
+
+    #[test]
+    fn test_probe_unconnected_node() {
+        let nets = nets();
+        let current = route(&nets);
+        let (_, probe) = route_probe(&nets, &current, Node::_20, &init_board()).unwrap();
+        let added: Vec<Crosspoint> = added_crosspoints(&current, &probe).collect();
+        assert!(!added.is_empty());
+        assert!(added.iter().all(|crosspoint| crosspoint.net_id == 10.into()));
+    }
+
+    #[test]
+    fn test_no_free_adc() {
+        let mut nets = nets();
+        nets.push(Net::from_iter(
+            10.into(),
+            AdcChannel::ALL.iter().map(|adc| adc.node()),
+        ));
+        let current = route(&nets);
+        assert!(matches!(
+            route_probe(&nets, &current, Node::_20, &init_board()),
+            Err(ProbeError::NoFreeAdc)
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{board, board::Node, SupplySwitchPos};
+
+/// Voltage, in millivolts
+pub type Millivolts = i32;
+
+/// One of the two power rails on the breadboard
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rail {
+    Top,
+    Bottom,
+}
+
+impl Rail {
+    pub const ALL: [Rail; 2] = [Rail::Top, Rail::Bottom];
+
+    /// Node connected to the (positive side of the) rail, if the rail is routable
+    pub fn node(&self) -> Option<Node> {
+        #[cfg(feature = "board-v4")]
+        {
+            None
+        }
+        #[cfg(feature = "board-v5")]
+        {
+            Some(match self {
+                Rail::Top => Node::TOP_RAIL,
+                Rail::Bottom => Node::BOTTOM_RAIL,
+            })
+        }
+    }
+
+    /// LEDs of the (positive side of the) rail, which do not belong to any node
+    pub fn leds(&self) -> &'static [usize] {
+        match self {
+            Rail::Top => board::leds::TOP_RAIL,
+            Rail::Bottom => board::leds::BOTTOM_RAIL,
+        }
+    }
+}
+
+/// Voltages of the power rails
+///
+/// The voltage is the one of the positive side of each rail, relative to GND (which the negative side is connected to).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rails {
+    pub top: Millivolts,
+    pub bottom: Millivolts,
+}
+
+impl Rails {
+    pub fn get(&self, rail: Rail) -> Millivolts {
+        match rail {
+            Rail::Top => self.top,
+            Rail::Bottom => self.bottom,
+        }
+    }
+
+    pub fn set(&mut self, rail: Rail, voltage: Millivolts) {
+        match rail {
+            Rail::Top => self.top = voltage,
+            Rail::Bottom => self.bottom = voltage,
+        }
+    }
+}
+
+impl From<SupplySwitchPos> for Rails {
+    fn from(pos: SupplySwitchPos) -> Self {
+        match pos {
+            SupplySwitchPos::_3V3 => Rails { top: 3300, bottom: 3300 },
+            SupplySwitchPos::_5V => Rails { top: 5000, bottom: 5000 },
+            SupplySwitchPos::_8V => Rails { top: 8000, bottom: -8000 },
+        }
+    }
+}
+
+impl core::fmt::Display for Rails {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "top ")?;
+        write_voltage(f, self.top)?;
+        write!(f, ", bottom ")?;
+        write_voltage(f, self.bottom)
+    }
+}
+
+/// Writes a voltage in volts, with two decimals (like `-8.00V`)
+pub fn write_voltage<W: core::fmt::Write>(w: &mut W, voltage: Millivolts) -> core::fmt::Result {
+    let sign = if voltage < 0 { "-" } else { "" };
+    let abs = voltage.unsigned_abs();
+    write!(w, "{}{}.{:02}V", sign, abs / 1000, (abs % 1000) / 10)
+}
+
+/// Parses a voltage in volts (like `3.3`, `-8` or `2.5V`), with up to three decimals
+pub fn parse_voltage(input: &str) -> Option<Millivolts> {
+    parse_milli(input.strip_suffix(['V', 'v']).unwrap_or(input))
+}
+
+/// Parses a decimal number with up to three decimals, returning it in thousandths
+pub(crate) fn parse_milli(input: &str) -> Option<i32> {
+    let (negative, input) = match input.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, input),
+    };
+    let (int, frac) = input.split_once('.').unwrap_or((input, ""));
+    if int.is_empty() && frac.is_empty() || frac.len() > 3 {
+        return None;
+    }
+    let digits = |s: &str| s.bytes().all(|c| c.is_ascii_digit());
+    if !digits(int) || !digits(frac) {
+        return None;
+    }
+    let int: i32 = if int.is_empty() { 0 } else { int.parse().ok()? };
+    // pad the fraction to three digits
+    let frac = frac.bytes().chain(core::iter::repeat(b'0')).take(3);
+    let frac = frac.fold(0, |value, c| value * 10 + (c - b'0') as i32);
+    let value = int.checked_mul(1000)?.checked_add(frac)?;
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_pos() {
+        let rails = Rails::from(SupplySwitchPos::_8V);
+        assert_eq!(rails.get(Rail::Top), 8000);
+        assert_eq!(rails.get(Rail::Bottom), -8000);
+        assert_eq!(SupplySwitchPos::from_rails(&rails), Some(SupplySwitchPos::_8V));
+        let mut rails = Rails::from(SupplySwitchPos::_5V);
+        rails.set(Rail::Bottom, 3300);
+        assert_eq!(SupplySwitchPos::from_rails(&rails), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let rails = Rails { top: 3300, bottom: -505 };
+        assert_eq!(format!("{}", rails), "top 3.30V, bottom -0.50V");
+    }
+
+    #[test]
+    fn test_parse_voltage() {
+        assert_eq!(parse_voltage("3.3"), Some(3300));
+        assert_eq!(parse_voltage("-8V"), Some(-8000));
+        assert_eq!(parse_voltage(".25"), Some(250));
+        assert_eq!(parse_voltage("1.234"), Some(1234));
+        assert_eq!(parse_voltage("1.2345"), None);
+        assert_eq!(parse_voltage("-"), None);
+        assert_eq!(parse_voltage("1e3"), None);
+        assert_eq!(parse_voltage("+1"), None);
+    }
+}
@@ -0,0 +1,42 @@
+use crate::Rails;
+
+/// Represents position of the supply switch
+///
+/// This value cannot be detected, it must be set manually by the user.
+/// Each position corresponds to a set of rail voltages (see [`Rails`]).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SupplySwitchPos {
+    _3V3,
+    _5V,
+    _8V,
+}
+
+const SSP_3V3: &str = "3V3";
+const SSP_5V: &str = "5V";
+const SSP_8V: &str = "8V";
+
+impl SupplySwitchPos {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            SSP_3V3 => Some(Self::_3V3),
+            SSP_5V => Some(Self::_5V),
+            SSP_8V => Some(Self::_8V),
+            _ => None,
+        }
+    }
+
+    /// Switch position which results in the given rail voltages, if any
+    pub fn from_rails(rails: &Rails) -> Option<Self> {
+        [Self::_3V3, Self::_5V, Self::_8V]
+            .into_iter()
+            .find(|pos| Rails::from(*pos) == *rails)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SupplySwitchPos::_3V3 => SSP_3V3,
+            SupplySwitchPos::_5V => SSP_5V,
+            SupplySwitchPos::_8V => SSP_8V,
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use core::mem::discriminant;
+use embassy_time::Duration;
+use heapless::Vec;
+use micromath::F32Ext;
+
+use crate::leds::Frame;
+use jumperless_common::{board, board::Node, types::set::NodeSet};
+
+/// Duration of a single animation frame (50 frames per second)
+pub const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+const MAX_OVERLAYS: usize = 8;
+const MAX_EFFECTS: usize = 4;
+
+const DEFAULTBRIGHTNESS: i32 = 32;
+
+/// Transient effect, drawn on top of the other layers until it finishes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Colorful fade in & out, played on startup
+    Startup,
+    /// The "rainbow bounce" animation
+    RainbowBounce,
+    /// Turns on a single LED for testing (and all others off), for half a second
+    TestLed(usize),
+    /// Blinks the LEDs of the given nodes (brighter than usual), and dims all others, for three seconds
+    Highlight(NodeSet<Node>),
+    /// Flashes the LEDs of the given nodes in red, for two seconds
+    Error(NodeSet<Node>),
+}
+
+impl Effect {
+    /// Render the `t`-th frame of this effect
+    ///
+    /// Returns `false` (without rendering anything) once the effect is finished.
+    fn render<const N: usize>(&self, t: u32, frame: &mut Frame<N>) -> bool {
+        match *self {
+            Effect::Startup => {
+                let j = 4 + 2 * t as i32;
+                if j > 2 * DEFAULTBRIGHTNESS + 2 {
+                    return false;
+                }
+                // fade in, then out again
+                let fade = if j < DEFAULTBRIGHTNESS / 3 {
+                    j * 3
+                } else {
+                    DEFAULTBRIGHTNESS - (j - DEFAULTBRIGHTNESS).clamp(0, DEFAULTBRIGHTNESS)
+                };
+                let offset = 1 + t as usize;
+                for i in 0..N {
+                    let led_index = (i + offset) % N;
+                    let mut h = (i as f32 * j as f32 * 0.1) / 255.0;
+                    let s = 0.99;
+                    let v = if board::leds::LOGO.contains(&led_index) {
+                        h = (189 + j) as f32 / 255.0;
+                        0.33
+                    } else {
+                        fade as f32 / 255.0
+                    };
+                    frame.set_hsv(led_index, (h, s, v));
+                }
+                true
+            }
+            Effect::RainbowBounce => {
+                // each step lasts two frames
+                let step = t / 2;
+                if step >= 80 {
+                    return false;
+                }
+                let j = if step < 40 { step } else { 79 - step };
+                for i in 0..N {
+                    frame.set_hsv(i, ((i as f32 / j as f32).sin(), 0.99, 0.1));
+                }
+                true
+            }
+            Effect::TestLed(index) => {
+                if t >= 25 {
+                    return false;
+                }
+                frame.clear();
+                if index < N {
+                    frame.set_rgb8(index, (32, 32, 32));
+                }
+                true
+            }
+            Effect::Highlight(nodes) => {
+                if t >= 150 {
+                    return false;
+                }
+                let original = frame.clone();
+                for i in 0..N {
+                    frame.scale(i, 64);
+                }
+                // on and off for 240ms each
+                let on = (t / 12) % 2 == 0;
+                for node in nodes.iter() {
+                    for &i in node.leds() {
+                        if on {
+                            frame.copy_led(i, &original);
+                            frame.scale(i, 4 * 256);
+                        } else {
+                            frame.set_rgb8(i, (0, 0, 0));
+                        }
+                    }
+                }
+                true
+            }
+            Effect::Error(nodes) => {
+                if t >= 100 {
+                    return false;
+                }
+                // on and off for 200ms each
+                let color = if (t / 10) % 2 == 0 { (0x60, 0, 0) } else { (0, 0, 0) };
+                for node in nodes.iter() {
+                    for &i in node.leds() {
+                        frame.set_rgb8(i, color);
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Marking drawn on top of the base layer, until the overlays are cleared
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+    /// Shows the LEDs of the given nodes brighter than usual
+    Highlight(NodeSet<Node>),
+}
+
+impl Overlay {
+    fn render<const N: usize>(&self, frame: &mut Frame<N>) {
+        match self {
+            Overlay::Highlight(nodes) => {
+                for node in nodes.iter() {
+                    for &i in node.leds() {
+                        frame.scale(i, 3 * 256);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Composes LED frames from multiple layers
+///
+/// From bottom to top, these are:
+/// - the base layer, usually reflecting the nets (see [`Frame::update_from_nets`])
+/// - [`Overlay`]s, which stay until they are cleared
+/// - transient [`Effect`]s, which are removed once they are finished. Multiple effects can run at
+///   the same time, the most recently started one being on top.
+///
+/// Changes to any layer are only visible after the next call to [`Animator::render`]. While effects
+/// are running, [`Animator::render`] must be called every [`FRAME_DURATION`].
+pub struct Animator<const N: usize> {
+    base: Frame<N>,
+    overlays: Vec<Overlay, MAX_OVERLAYS>,
+    // running effects, with the frame they were started at
+    effects: Vec<(Effect, u32), MAX_EFFECTS>,
+    frame: u32,
+    dirty: bool,
+}
+
+impl<const N: usize> Animator<N> {
+    pub const fn new() -> Self {
+        Self {
+            base: Frame::new(),
+            overlays: Vec::new(),
+            effects: Vec::new(),
+            frame: 0,
+            dirty: true,
+        }
+    }
+
+    /// Access the base layer, to modify it
+    pub fn base_mut(&mut self) -> &mut Frame<N> {
+        self.dirty = true;
+        &mut self.base
+    }
+
+    /// Add an overlay, unless it was added before. If there are too many overlays, the oldest one is dropped.
+    pub fn add_overlay(&mut self, overlay: Overlay) {
+        if self.overlays.contains(&overlay) {
+            return;
+        }
+        if self.overlays.is_full() {
+            self.overlays.remove(0);
+        }
+        _ = self.overlays.push(overlay);
+        self.dirty = true;
+    }
+
+    /// Remove all overlays
+    pub fn clear_overlays(&mut self) {
+        self.overlays.clear();
+        self.dirty = true;
+    }
+
+    /// Start an effect
+    ///
+    /// If an effect of the same kind is already running, it is replaced (i.e. restarted).
+    /// If too many effects are running, the oldest one is stopped.
+    pub fn start(&mut self, effect: Effect) {
+        self.effects
+            .retain(|(running, _)| discriminant(running) != discriminant(&effect));
+        if self.effects.is_full() {
+            self.effects.remove(0);
+        }
+        _ = self.effects.push((effect, self.frame));
+        self.dirty = true;
+    }
+
+    /// Returns true while effects are running
+    pub fn is_animating(&self) -> bool {
+        !self.effects.is_empty()
+    }
+
+    /// Returns true if the next call to [`Animator::render`] would produce a different frame
+    pub fn needs_render(&self) -> bool {
+        self.dirty || self.is_animating()
+    }
+
+    /// Compose the next frame from all layers, and advance running effects
+    pub fn render(&mut self, frame: &mut Frame<N>) {
+        frame.clone_from(&self.base);
+        for overlay in &self.overlays {
+            overlay.render(frame);
+        }
+        let now = self.frame;
+        self.effects
+            .retain(|(effect, start)| effect.render(now.wrapping_sub(*start), frame));
+        self.frame = self.frame.wrapping_add(1);
+        self.dirty = false;
+    }
+}
+
+impl<const N: usize> Default for Animator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
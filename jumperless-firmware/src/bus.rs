@@ -1,4 +1,11 @@
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Sender};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    channel::Sender,
+    mutex::Mutex,
+    pubsub::{PubSubChannel, Subscriber},
+    signal::Signal,
+};
+use jumperless_common::{board::Node, types::set::NodeSet};
 
 /// Size of the message channel for each task.
 pub const CHANNEL_SIZE: usize = 8;
@@ -20,3 +27,101 @@ pub trait BusMessage: Sized {
 pub async fn inject<'a, T: BusMessage>(message: T) {
     T::sender().send(message).await;
 }
+
+/// Place where replies to requests are delivered, see [`request`]
+///
+/// Tasks which accept requests define one of these (as a `static`) for each type of reply.
+/// Requests using the same slot are processed one after another.
+pub struct ReplySlot<T: 'static> {
+    // sequence number of the most recent request
+    lock: Mutex<ThreadModeRawMutex, u32>,
+    signal: Signal<ThreadModeRawMutex, (u32, T)>,
+}
+
+impl<T> ReplySlot<T> {
+    pub const fn new() -> Self {
+        Self {
+            lock: Mutex::new(0),
+            signal: Signal::new(),
+        }
+    }
+}
+
+/// Handle to reply to a request, carried by the request message
+///
+/// Must be used exactly once, by calling [`Reply::send`]. Otherwise the requesting task waits forever.
+pub struct Reply<T: 'static> {
+    signal: &'static Signal<ThreadModeRawMutex, (u32, T)>,
+    sequence: u32,
+}
+
+impl<T> Reply<T> {
+    pub fn send(self, value: T) {
+        self.signal.signal((self.sequence, value));
+    }
+}
+
+/// Inject a request message into the bus, and wait for the reply
+///
+/// `message` receives the [`Reply`] handle, and constructs the message to send.
+///
+/// Replies are tagged with a sequence number, so a late reply to an earlier request (whose requester
+/// stopped waiting) is not mistaken for the reply to this one.
+///
+/// Example:
+///     let result = bus::request(&net_manager::REPLY, |reply| net_manager::Message::Reset(reply)).await;
+///
+pub async fn request<T, M: BusMessage>(slot: &'static ReplySlot<T>, message: impl FnOnce(Reply<T>) -> M) -> T {
+    let mut sequence = slot.lock.lock().await;
+    *sequence = sequence.wrapping_add(1);
+    inject(message(Reply { signal: &slot.signal, sequence: *sequence })).await;
+    loop {
+        let (replied_to, value) = slot.signal.wait().await;
+        if replied_to == *sequence {
+            return value;
+        }
+    }
+}
+
+/// Number of events buffered for each subscriber. If a subscriber falls behind further, it misses the oldest ones.
+const EVENT_CAPACITY: usize = 8;
+
+/// Maximum number of event subscribers
+const MAX_SUBSCRIBERS: usize = 4;
+
+/// Notification about a change of state, see [`publish`]
+#[derive(Clone)]
+pub enum Event {
+    /// Nets (or their colors) changed
+    NetsChanged,
+    /// Changed nets could not be routed (and were reverted). Holds the nodes of the failing net.
+    RoutingFailed(NodeSet<Node>),
+    /// Rail voltages changed
+    RailsChanged,
+    /// Settings (like LED brightness) changed
+    SettingsChanged,
+    /// The shell's USB serial port was connected
+    UsbConnected,
+    /// The shell's USB serial port was disconnected
+    UsbDisconnected,
+}
+
+static EVENTS: PubSubChannel<ThreadModeRawMutex, Event, EVENT_CAPACITY, MAX_SUBSCRIBERS, 0> = PubSubChannel::new();
+
+pub type EventSubscriber = Subscriber<'static, ThreadModeRawMutex, Event, EVENT_CAPACITY, MAX_SUBSCRIBERS, 0>;
+
+/// Publish an event, to all current subscribers
+///
+/// Unlike [`inject`], this never waits: subscribers which have fallen behind miss their oldest event instead.
+pub fn publish(event: Event) {
+    EVENTS.immediate_publisher().publish_immediate(event);
+}
+
+/// Subscribe to events
+///
+/// Only events published after subscribing are received, so tasks should subscribe right when they start.
+///
+/// Panics if there are more than [`MAX_SUBSCRIBERS`] subscribers.
+pub fn subscribe() -> EventSubscriber {
+    EVENTS.subscriber().unwrap()
+}
@@ -0,0 +1,61 @@
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::ptr::addr_of_mut;
+
+use embassy_rp::pac;
+pub use jumperless_common::crash_log::{CrashKind, CrashLog, ENCODED_SIZE};
+
+/// Crash record written before a reset. Located in RAM which is not initialized on startup, so it
+/// survives resets (but not power cycles). Protected by a checksum, since it holds garbage after power on.
+#[link_section = ".uninit.crash_log"]
+static mut PENDING: MaybeUninit<[u8; ENCODED_SIZE]> = MaybeUninit::uninit();
+
+/// Record a crash, to be picked up by [`take`] after the following reset
+pub fn record(log: &CrashLog) {
+    // SAFETY: only accessed from the thread executor and the panic handler, which never returns
+    unsafe { addr_of_mut!(PENDING).write(MaybeUninit::new(log.encode())) };
+}
+
+/// Forget a crash recorded with [`record`], before an intentional reset
+pub fn clear() {
+    // SAFETY: see `record`
+    unsafe { addr_of_mut!(PENDING).write(MaybeUninit::new([0; ENCODED_SIZE])) };
+}
+
+/// Record a [`CrashKind::WatchdogTimeout`], which is reported if the device is reset by the hardware
+/// watchdog before any other crash is recorded (for example when the executor itself got stuck)
+///
+/// Called during startup, and whenever the device recovers from a recorded crash.
+pub fn arm() {
+    record(&CrashLog::new(CrashKind::WatchdogTimeout));
+}
+
+/// Returns the crash which caused the last reset, if any
+///
+/// Must be called once during startup, before [`arm`].
+pub fn take() -> Option<CrashLog> {
+    // SAFETY: see `record`. Any bit pattern is a valid `[u8; N]`.
+    let pending = unsafe { addr_of_mut!(PENDING).read().assume_init() };
+    clear();
+    let crash = CrashLog::decode(&pending)?;
+    // other resets (like the reset button) don't clear RAM either
+    if crash.kind == CrashKind::WatchdogTimeout && !pac::WATCHDOG.reason().read().timer() {
+        return None;
+    }
+    Some(crash)
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+
+    let mut log = CrashLog::new(CrashKind::Panic);
+    _ = write!(log, "{}", info);
+    record(&log);
+
+    defmt::error!("{}", defmt::Display2Format(info));
+
+    // Like `panic_probe`: lets an attached probe show a backtrace. Otherwise the hardware watchdog resets the device.
+    cortex_m::asm::udf()
+}
@@ -0,0 +1,40 @@
+use core::fmt::Write;
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+
+use crate::task::settings::SettingsFlash;
+
+/// Firmware version, from `Cargo.toml`
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Git commit the firmware was built from (set by `build.rs`)
+pub const GIT_HASH: &str = env!("GIT_HASH");
+
+pub use jumperless_common::board::REVISION as BOARD_REVISION;
+
+/// Serial number, as hex digits of the 64 bit flash unique ID
+pub type SerialNumber = heapless::String<16>;
+
+static SERIAL_NUMBER: Mutex<ThreadModeRawMutex, SerialNumber> = Mutex::new(heapless::String::new());
+
+/// Read the unique ID of the flash chip, and derive the serial number from it
+///
+/// Called once during startup, before the flash is handed to the `settings` task.
+pub async fn init(flash: &mut SettingsFlash) -> SerialNumber {
+    let mut unique_id = [0; 8];
+    if let Err(e) = flash.blocking_unique_id(&mut unique_id) {
+        defmt::error!("Failed to read flash unique ID: {}", e);
+    }
+    let mut serial_number = SerialNumber::new();
+    for byte in unique_id {
+        _ = write!(serial_number, "{:02X}", byte);
+    }
+    defmt::info!("Serial number: {}", serial_number.as_str());
+    *SERIAL_NUMBER.lock().await = serial_number.clone();
+    serial_number
+}
+
+/// Returns the serial number, see [`init`]
+pub async fn serial_number() -> SerialNumber {
+    SERIAL_NUMBER.lock().await.clone()
+}
@@ -1,34 +1,31 @@
 use crate::nets::Nets;
+use crate::settings::Settings;
+use jumperless_common::Rails;
 use embassy_rp::dma::{AnyChannel, Channel};
 use embassy_rp::pio::{
     Common, Config, FifoJoin, Instance, PioPin, ShiftConfig, ShiftDirection, StateMachine,
 };
 use embassy_rp::{clocks, into_ref, Peripheral, PeripheralRef};
-use embassy_time::{Duration, Timer};
+use embassy_time::Timer;
 use fixed::types::U24F8;
 use fixed_macro::fixed;
 use micromath::F32Ext;
-use jumperless_common::{board::Node, types::Node as NodeTrait};
+use jumperless_common::brightness::{brightness_scale, estimate_current, limit_scale, scale_component, FULL_SCALE};
+use jumperless_common::frame::render_nets;
 
-const DEFAULTBRIGHTNESS: i32 = 32;
-
-/// In-memory buffer for LED state
+/// In-memory buffer for LED colors
 ///
-/// The LED colors are held in an in-memory buffer. The buffer can be manipulated
-/// by calling [`set_rgb8`], [`set_rgb`] or [`set_hsv`], and then written to the
-/// LEDs all at once by calling [`flush`].
+/// The buffer can be manipulated by calling [`set_rgb8`], [`set_rgb`] or [`set_hsv`],
+/// and then written to the LEDs all at once by passing it to [`Leds::flush`].
 ///
-pub struct Leds<'d, P: Instance, const S: usize, const N: usize> {
-    ws2812: Ws2812<'d, P, S, N>,
+#[derive(Clone)]
+pub struct Frame<const N: usize> {
     words: [u32; N],
 }
 
-impl<'d, P: Instance, const S: usize, const N: usize> Leds<'d, P, S, N> {
-    pub fn new(ws2812: Ws2812<'d, P, S, N>) -> Self {
-        Self {
-            ws2812,
-            words: [0; N],
-        }
+impl<const N: usize> Frame<N> {
+    pub const fn new() -> Self {
+        Self { words: [0; N] }
     }
 
     /// Set LED at given index to color in RGB colorspace
@@ -58,139 +55,111 @@ impl<'d, P: Instance, const S: usize, const N: usize> Leds<'d, P, S, N> {
         self.set_rgb(i, (r, g, b));
     }
 
-    /// Update LEDs with change made by the `set_*` methods.
-    pub async fn flush(&mut self) {
-        self.ws2812.write_raw(&self.words).await;
+    /// Scale the color of the LED at given index by `scale / 256`
+    ///
+    /// Color components saturate at full intensity.
+    pub fn scale(&mut self, i: usize, scale: u32) {
+        assert!(i < N);
+        let word = self.words[i];
+        let component = |shift: u32| (((word >> shift) & 0xFF) * scale / 256).min(0xFF) << shift;
+        self.words[i] = component(24) | component(16) | component(8);
+    }
+
+    /// Copy the color of the LED at given index from another frame
+    pub fn copy_led(&mut self, i: usize, other: &Frame<N>) {
+        assert!(i < N);
+        self.words[i] = other.words[i];
     }
 
-    /// Turn off all LEDs (set all colors to 0 and flush)
-    pub async fn off(&mut self) {
+    /// Turn off all LEDs (set all colors to 0)
+    pub fn clear(&mut self) {
         self.words.fill(0);
-        self.flush().await;
     }
 
-    /// Play startup animation
-    ///
-    /// Turns all LEDs off when done.
-    pub async fn startup_colors(&mut self) {
-        let mut offset = 1;
-        let mut fade;
-        let mut done = false;
-        for j in (4..162).step_by(2) {
-            if j < DEFAULTBRIGHTNESS / 3 {
-                fade = j * 3;
-            } else {
-                let mut fadeout = j - DEFAULTBRIGHTNESS;
-                if fadeout < 0 {
-                    fadeout = 0;
-                }
-                if fadeout > DEFAULTBRIGHTNESS {
-                    fadeout = DEFAULTBRIGHTNESS;
-                    done = true;
-                }
-                fade = DEFAULTBRIGHTNESS - fadeout;
-            }
-
-            for i in 0..N {
-                let led_index = (i + offset) % N;
-                let mut h = (i as f32 * j as f32 * 0.1) / 255.0;
-                let s = 0.99;
-                let v = if led_index == 110 {
-                    h = (189 + j) as f32 / 255.0;
-                    0.33
-                } else {
-                    fade as f32 / 255.0
-                };
-                self.set_hsv(led_index, (h, s, v));
-            }
-            self.flush().await;
-
-            offset += 1;
-
-            if done {
-                break;
-            } else {
-                Timer::after_millis(14).await;
-            }
-        }
-        Timer::after_millis(2).await;
-        self.off().await;
+    /// Set colors to reflect the given nets and rails (see [`render_nets`]), turning off all other LEDs
+    pub fn update_from_nets(&mut self, nets: &Nets, rails: &Rails) {
+        self.clear();
+        render_nets(&nets.nets, &nets.colors, rails, |i, color| self.set_rgb8(i, color));
     }
 
-    /// Play "rainbow bounce" animation
-    ///
-    /// Turns all LEDs off when done.
-    pub async fn rainbow_bounce(&mut self, wait: Duration) {
-        for j in (0..40).chain((0..40).rev()) {
-            for i in 0..N {
-                self.set_hsv(i, ((i as f32 / j as f32).sin(), 0.99, 0.1));
-            }
-            self.flush().await;
-            Timer::after(wait + Duration::from_millis(j / 20)).await;
-        }
+    /// All color components of all LEDs
+    pub fn components(&self) -> impl Iterator<Item = u8> + '_ {
+        self.words.iter().flat_map(|word| [(word >> 24) as u8, (word >> 16) as u8, (word >> 8) as u8])
+    }
 
-        Timer::after_millis(2).await;
-        self.off().await;
+    /// Color of the LED at given index, in RGB colorspace
+    pub fn rgb8(&self, i: usize) -> (u8, u8, u8) {
+        assert!(i < N);
+        let word = self.words[i];
+        ((word >> 16) as u8, (word >> 24) as u8, (word >> 8) as u8)
     }
+}
 
-    /// Update LEDs to reflect the given nets
-    ///
-    /// Flushes changes to the board when done.
-    ///
-    /// In detail, this:
-    /// - lights up the breadboard & nano nodes corresponding to each net
-    /// - lights up the rails (respecting `nets.supply_switch_pos`)
-    /// - adds headerglow to the unused nano LEDs
-    /// - turns off all other LEDs
-    pub async fn update_from_nets(&mut self, nets: &Nets) {
-        self.words.fill(0);
+impl<const N: usize> Default for Frame<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        for i in 80..=109 {
-            self.set_rgb8(i, (0x02, 0x00, 0x08)); // headerglow
-        }
+/// Writes [`Frame`]s to the LEDs
+///
+/// Colors in a frame are at full brightness. The global brightness (see [`set_brightness`])
+/// is only applied while flushing. If the resulting colors would draw more current than the
+/// power budget (see [`set_power_budget`]) allows, all of them are scaled down further.
+///
+pub struct Leds<'d, P: Instance, const S: usize, const N: usize> {
+    ws2812: Ws2812<'d, P, S, N>,
+    // last flushed frame, scaled
+    output: [u32; N],
+    // brightness scaling factor, see `jumperless_common::brightness`
+    scale: u32,
+    power_budget_ma: u32,
+}
 
-        for net in &nets.nets {
-            for node in net.nodes.iter() {
-                if let Some(pixel) = node_pixel(node) {
-                    self.set_rgb8(pixel as usize, nets.color(net.id));
-                }
-            }
-        }
-        let gnd = nets.color(nets.nets[0].id);
-        let v5 = nets.color(nets.nets[1].id);
-        let v33 = nets.color(nets.nets[2].id);
-
-        self.set_rgb8(83, gnd);
-        self.set_rgb8(108, gnd);
-        self.set_rgb8(109, v5);
-        self.set_rgb8(96, v33);
-        self.set_rgb8(106, v5);
-
-        for i in [
-            71, 72, 75, 76, 79, // top negative rail
-            68, 67, 64, 63, 60, // bottom negative rail
-        ] {
-            self.set_rgb8(i, gnd);
+impl<'d, P: Instance, const S: usize, const N: usize> Leds<'d, P, S, N> {
+    pub fn new(ws2812: Ws2812<'d, P, S, N>) -> Self {
+        Self {
+            ws2812,
+            output: [0; N],
+            scale: FULL_SCALE,
+            power_budget_ma: Settings::DEFAULT.power_budget as u32,
         }
+    }
 
-        let v8p = (0x30, 0x1A, 0x02);
-        let v8n = (0x12, 0x09, 0x32);
+    /// Set maximum current (in mA) the LEDs may draw together
+    ///
+    /// This only accounts for current drawn by lit LEDs, not the quiescent current of each LED.
+    /// Takes effect on the next [`flush`].
+    pub fn set_power_budget(&mut self, milliamps: u32) {
+        self.power_budget_ma = milliamps;
+    }
 
-        let (top, bottom) = match nets.supply_switch_pos {
-            crate::nets::SupplySwitchPos::_3V3 => (v33, v33),
-            crate::nets::SupplySwitchPos::_5V => (v5, v5),
-            crate::nets::SupplySwitchPos::_8V => (v8p, v8n),
-        };
+    /// Set global brightness, in percent (0 - 100)
+    ///
+    /// The brightness is gamma corrected, so that it is perceived linearly. Takes effect on the next [`flush`].
+    pub fn set_brightness(&mut self, percent: u8) {
+        self.scale = brightness_scale(percent);
+    }
 
-        for i in [70, 73, 74, 77, 78] {
-            self.set_rgb8(i, top);
+    /// Write the given frame to the LEDs
+    pub async fn flush(&mut self, frame: &Frame<N>) {
+        let scale = self.limited_scale(frame);
+        for (output, word) in self.output.iter_mut().zip(frame.words.iter()) {
+            *output = scale_word(*word, scale);
         }
+        self.ws2812.write_raw(&self.output).await;
+    }
 
-        for i in [69, 66, 65, 62, 61] {
-            self.set_rgb8(i, bottom);
+    /// Brightness scaling factor, reduced as far as needed to stay within the power budget
+    fn limited_scale(&self, frame: &Frame<N>) -> u32 {
+        let scale = limit_scale(frame.components(), self.scale, self.power_budget_ma);
+        if scale < self.scale {
+            defmt::trace!(
+                "Limiting LED current: {}mA requested",
+                estimate_current(frame.components(), self.scale) / 1000
+            );
         }
-
-        self.flush().await;
+        scale
     }
 }
 
@@ -293,46 +262,8 @@ pub fn mix(a: f32, b: f32, t: f32) -> f32 {
     a * (1. - t) + b * t
 }
 
-#[cfg(feature = "board-v5")]
-fn node_pixel(_node: Node) -> Option<u8> {
-    // FIXME: implement LED stuff for V5
-    None
-}
-
-#[cfg(feature = "board-v4")]
-fn node_pixel(node: Node) -> Option<u8> {
-    if node.id() < 60 {
-        // breadboard node
-        return Some(node.id());
-    }
-    use Node::*;
-    match node {
-        NANO_D0 => Some(81),
-        NANO_D1 => Some(80),
-        NANO_D2 => Some(84),
-        NANO_D3 => Some(85),
-        NANO_D4 => Some(86),
-        NANO_D5 => Some(87),
-        NANO_D6 => Some(88),
-        NANO_D7 => Some(89),
-        NANO_D8 => Some(90),
-        NANO_D9 => Some(91),
-        NANO_D10 => Some(92),
-        NANO_D11 => Some(93),
-        NANO_D12 => Some(94),
-        NANO_D13 => Some(95),
-        // (96 is 3V3)
-        NANO_AREF => Some(97),
-        NANO_A0 => Some(98),
-        NANO_A1 => Some(99),
-        NANO_A2 => Some(100),
-        NANO_A3 => Some(101),
-        NANO_A4 => Some(102),
-        NANO_A5 => Some(103),
-        NANO_A6 => Some(104),
-        NANO_A7 => Some(105),
-        // (106 is 5V)
-        NANO_RESET => Some(107),
-        _ => None,
-    }
+/// Scales each color component of a GRB word, see [`scale_component`]
+fn scale_word(word: u32, scale: u32) -> u32 {
+    let component = |shift: u32| (scale_component((word >> shift) as u8, scale) as u32) << shift;
+    component(24) | component(16) | component(8)
 }
@@ -8,32 +8,58 @@
 
 use ch446q::Ch446q;
 use embassy_executor::Spawner;
-use embassy_futures::join::join;
+use embassy_futures::join::join3;
 use embassy_rp::bind_interrupts;
-use embassy_rp::gpio::{Level, Output};
-use embassy_rp::peripherals::{PIO0, PIO1, USB};
+use embassy_rp::gpio::{Level, Output, Pull};
+use embassy_rp::peripherals::{I2C0, PIO0, PIO1, UART0, USB};
 use embassy_rp::watchdog::Watchdog;
-use embassy_rp::{pio, usb};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
-use embassy_sync::mutex::Mutex;
+use embassy_rp::{adc, i2c, pio, uart, usb};
+use embassy_embedded_hal::shared_bus::{asynch::i2c::I2cDevice, I2cDeviceError};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
 use embassy_usb::class::cdc_acm;
-use {defmt_rtt as _, panic_probe as _};
+use static_cell::StaticCell;
+use defmt_rtt as _;
 
 /// Driver for an array of 12 CH446Q crosspoint switches
 pub mod ch446q;
 /// LED functionality / hardware integration (WS2812 driver)
 pub mod leds;
 
+/// Frame-based LED animations, composed from multiple layers
+pub mod animation;
+
 pub mod nets;
 
+/// Application state (nets, rails, settings), shared between tasks
+///
+/// Changes to the state are announced as [`bus::Event`]s.
+pub mod state;
+
+/// Settings which are persisted in flash
+pub mod settings;
+
+/// Information identifying the device and firmware
+pub mod device_info;
+
+/// Records the cause of crashes (panics, stalled tasks), so it can be reported after the reset
+///
+/// Also provides the panic handler.
+pub mod crash_log;
+
 /// USB-serial based shell
 pub mod shell;
 
+/// Bridge between a second USB-serial interface and the UART (`RP_UART_RX` / `RP_UART_TX` nodes)
+pub mod uart_bridge;
+
 /// The bus routes messages to one of the top-level tasks
 ///
 /// Most of the [`task`]s define a type of [`bus::BusMessage`] that controls the task's behavior.
 ///
 /// Other tasks can use [`bus::inject`] to send these messages to the respective recipient.
+///
+/// In addition, changes of state are announced as [`bus::Event`]s, which any number of tasks can
+/// subscribe to (see [`bus::publish`] and [`bus::subscribe`]).
 pub mod bus;
 
 /// Top-level tasks; always running.
@@ -43,24 +69,38 @@ bind_interrupts!(struct Irqs {
     PIO0_IRQ_0 => pio::InterruptHandler<PIO0>;
     PIO1_IRQ_0 => pio::InterruptHandler<PIO1>;
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
+    UART0_IRQ => uart::BufferedInterruptHandler<UART0>;
+    I2C0_IRQ => i2c::InterruptHandler<I2C0>;
+    ADC_IRQ_FIFO => adc::InterruptHandler;
 });
 
-static NETS: Mutex<ThreadModeRawMutex, Option<nets::Nets>> = Mutex::new(None);
+type I2cBus = i2c::I2c<'static, I2C0, i2c::Async>;
+
+static I2C_BUS: StaticCell<Mutex<ThreadModeRawMutex, I2cBus>> = StaticCell::new();
+
+/// Handle to the I2C bus shared by the DAC and the current sensor
+pub type SharedI2c = I2cDevice<'static, ThreadModeRawMutex, I2cBus>;
+
+pub type I2cError = I2cDeviceError<i2c::Error>;
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    // Initialize shared NETS (FIXME: rename this to STATE? it's more than just nets...)
-    {
-        *(NETS.lock().await) = Some(nets::Nets::default());
-    }
-
     // Configure PIO0 to control ws2812 LEDs
     let pio::Pio {
         mut common, sm0, ..
     } = pio::Pio::new(p.PIO0, Irqs);
-    let leds = leds::Leds::new(leds::Ws2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_25));
+    let mut leds = leds::Leds::new(leds::Ws2812::new(&mut common, sm0, p.DMA_CH0, p.PIN_25));
+
+    // Load persisted settings
+    let mut flash = task::settings::SettingsFlash::new_blocking(p.FLASH);
+    let settings = task::settings::load(&mut flash).await;
+    state::init(settings).await;
+    task::settings::load_crash_log(&mut flash).await;
+    let serial_number = device_info::init(&mut flash).await;
+    leds.set_brightness(settings.brightness);
+    leds.set_power_budget(settings.power_budget as u32);
 
     // Configure PIO1 to control ch446q chips
     let pio::Pio {
@@ -95,6 +135,27 @@ async fn main(spawner: Spawner) {
 
     ch446q.reset().await;
 
+    // Configure I2C0, connected to the DAC and the current sensor
+    let mut i2c_config = i2c::Config::default();
+    i2c_config.frequency = 400_000;
+    let i2c_bus = I2C_BUS.init(Mutex::new(i2c::I2c::new_async(p.I2C0, p.PIN_5, p.PIN_4, Irqs, i2c_config)));
+    let dac = jumperless_common::dac::Mcp4728::new(I2cDevice::new(i2c_bus), jumperless_common::dac::MCP4728_ADDRESS);
+    let current_sensor = jumperless_common::current_sense::Ina219::new(
+        I2cDevice::new(i2c_bus),
+        jumperless_common::current_sense::INA219_ADDRESS,
+    );
+
+    // Configure the ADC, with inputs connected to the ADC0-ADC3 nodes
+    let adcs = task::measure::Adcs {
+        adc: adc::Adc::new(p.ADC, Irqs, adc::Config::default()),
+        channels: [
+            adc::Channel::new_pin(p.PIN_26, Pull::None),
+            adc::Channel::new_pin(p.PIN_27, Pull::None),
+            adc::Channel::new_pin(p.PIN_28, Pull::None),
+            adc::Channel::new_pin(p.PIN_29, Pull::None),
+        ],
+    };
+
     // Make one example connection, between breadboard nodes [2] and [3]
 
     // ch446q.set_chip(Chip::A);
@@ -143,6 +204,8 @@ async fn main(spawner: Spawner) {
     spawner
         .spawn(task::watchdog::main(Watchdog::new(p.WATCHDOG)))
         .unwrap();
+    defmt::info!("Spawning task: settings");
+    spawner.spawn(task::settings::main(flash)).unwrap();
     defmt::info!("Spawning task: leds");
     spawner.spawn(task::leds::main(leds)).unwrap();
 
@@ -150,13 +213,17 @@ async fn main(spawner: Spawner) {
     spawner
         .spawn(task::net_manager::main(ch446q))
         .unwrap();
+    defmt::info!("Spawning task: dac");
+    spawner.spawn(task::dac::main(dac)).unwrap();
+    defmt::info!("Spawning task: measure");
+    spawner.spawn(task::measure::main(current_sensor, adcs)).unwrap();
 
     // Initialize USB driver
     let usb_driver = usb::Driver::new(p.USB, Irqs);
     let mut config = embassy_usb::Config::new(0x1D50, 0xACAB);
     config.manufacturer = Some("Architeuthis Flux");
     config.product = Some("Jumperless");
-    config.serial_number = Some("0");
+    config.serial_number = Some(serial_number.as_str());
     config.device_class = 0xEF;
     config.device_sub_class = 0x02;
     config.device_protocol = 0x01;
@@ -169,6 +236,7 @@ async fn main(spawner: Spawner) {
     let mut control_buf = [0; 64];
 
     let mut state = cdc_acm::State::new();
+    let mut uart_bridge_state = cdc_acm::State::new();
 
     let mut builder = embassy_usb::Builder::new(
         usb_driver,
@@ -180,6 +248,7 @@ async fn main(spawner: Spawner) {
     );
 
     let mut class = cdc_acm::CdcAcmClass::new(&mut builder, &mut state, 64);
+    let mut uart_bridge_class = cdc_acm::CdcAcmClass::new(&mut builder, &mut uart_bridge_state, 64);
 
     let mut usb = builder.build();
 
@@ -189,11 +258,25 @@ async fn main(spawner: Spawner) {
         loop {
             class.wait_connection().await;
             defmt::info!("USB Serial Connected");
-            let mut shell: shell::Shell<'_, '_, 62> = shell::Shell::new(&mut class);
+            bus::publish(bus::Event::UsbConnected);
+            let mut shell: shell::Shell<'_, '_, 62> =
+                shell::Shell::new(shell::UsbSerial::new(&mut class), shell::BusExecutor);
             let _ = shell.run().await;
             defmt::info!("USB Serial Disconnected");
+            bus::publish(bus::Event::UsbDisconnected);
+        }
+    };
+
+    let mut uart_bridge = uart_bridge::UartBridge::new(p.UART0, p.PIN_0, p.PIN_1);
+
+    let uart_bridge_future = async {
+        loop {
+            uart_bridge_class.wait_connection().await;
+            defmt::info!("UART bridge Connected");
+            let _ = uart_bridge.run(&mut uart_bridge_class).await;
+            defmt::info!("UART bridge Disconnected");
         }
     };
 
-    join(usb_future, shell_future).await;
+    join3(usb_future, shell_future, uart_bridge_future).await;
 }
@@ -2,10 +2,10 @@ use jumperless_common::{types::NetId, board::Node, types::Net};
 
 use heapless::Vec;
 
-const MAX_NETS: usize = 64;
+pub const MAX_NETS: usize = 64;
 
+#[derive(Clone)]
 pub struct Nets {
-    pub supply_switch_pos: SupplySwitchPos,
     pub nets: Vec<Net<Node>, MAX_NETS>,
     pub colors: Vec<(u8, u8, u8), MAX_NETS>,
 }
@@ -62,12 +62,15 @@ impl Nets {
     pub fn color(&self, net_id: NetId) -> (u8, u8, u8) {
         self.colors[net_id.index()]
     }
+
+    pub fn set_color(&mut self, net_id: NetId, color: (u8, u8, u8)) {
+        self.colors[net_id.index()] = color;
+    }
 }
 
 impl Default for Nets {
     fn default() -> Self {
         let mut nets = Self {
-            supply_switch_pos: SupplySwitchPos::_5V,
             nets: Vec::new(),
             colors: Vec::new(),
         };
@@ -92,36 +95,3 @@ impl Default for Nets {
         nets
     }
 }
-
-/// Represents position of the supply switch
-///
-/// This value cannot be detected, it must be set manually by the user.
-/// Only affects the color of the rail LEDs.
-pub enum SupplySwitchPos {
-    _3V3,
-    _5V,
-    _8V,
-}
-
-const SSP_3V3: &str = "3V3";
-const SSP_5V: &str = "5V";
-const SSP_8V: &str = "8V";
-
-impl SupplySwitchPos {
-    pub fn parse(input: &str) -> Option<Self> {
-        match input {
-            SSP_3V3 => Some(Self::_3V3),
-            SSP_5V => Some(Self::_5V),
-            SSP_8V => Some(Self::_8V),
-            _ => None,
-        }
-    }
-
-    pub fn label(&self) -> &'static str {
-        match self {
-            SupplySwitchPos::_3V3 => SSP_3V3,
-            SupplySwitchPos::_5V => SSP_5V,
-            SupplySwitchPos::_8V => SSP_8V,
-        }
-    }
-}
@@ -0,0 +1,65 @@
+use jumperless_common::color::Palette;
+
+/// Settings which are persisted across resets
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// Global LED brightness, in percent (0 - 100)
+    pub brightness: u8,
+    /// Palette to pick colors for new nets from
+    pub palette: Palette,
+    /// Maximum current (in mA) all LEDs together may draw, see `leds::Leds::set_power_budget`
+    pub power_budget: u16,
+}
+
+/// Marks a valid settings record. Bump the last byte when the layout changes incompatibly.
+///
+/// Adding fields is compatible, as long as the erased state (`0xFF`) decodes to the default.
+const MAGIC: [u8; 4] = *b"JLS\x01";
+
+/// Size of an encoded settings record
+pub const ENCODED_SIZE: usize = 8;
+
+impl Settings {
+    pub const DEFAULT: Settings = Settings {
+        brightness: 100,
+        palette: Palette::Random,
+        power_budget: 300,
+    };
+
+    /// Encode settings for storage in flash
+    pub fn encode(&self) -> [u8; ENCODED_SIZE] {
+        let mut buf = [0xFF; ENCODED_SIZE];
+        buf[..4].copy_from_slice(&MAGIC);
+        buf[4] = self.brightness;
+        buf[5] = Palette::ALL.iter().position(|p| *p == self.palette).unwrap_or(0) as u8;
+        buf[6..8].copy_from_slice(&self.power_budget.to_le_bytes());
+        buf
+    }
+
+    /// Decode settings previously encoded with [`Settings::encode`]
+    ///
+    /// Returns `None` if the data does not contain valid settings (e.g. erased flash).
+    pub fn decode(buf: &[u8; ENCODED_SIZE]) -> Option<Self> {
+        if buf[..4] != MAGIC || buf[4] > 100 {
+            return None;
+        }
+        Some(Self {
+            brightness: buf[4],
+            palette: Palette::ALL
+                .get(buf[5] as usize)
+                .copied()
+                .unwrap_or(Self::DEFAULT.palette),
+            power_budget: match u16::from_le_bytes([buf[6], buf[7]]) {
+                // not written by older firmware
+                0xFFFF => Self::DEFAULT.power_budget,
+                power_budget => power_budget,
+            },
+        })
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
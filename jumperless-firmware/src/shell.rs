@@ -1,91 +1,23 @@
+use embassy_futures::select::{select, Either};
 use embassy_rp::{peripherals::USB, usb::Driver};
-// use heapless::Vec;
+use embassy_time::{Duration, Instant, Ticker, Timer};
 use embassy_usb::{class::cdc_acm::CdcAcmClass, driver::EndpointError};
-use line_buffer::LineBuffer;
-use jumperless_common::board::Node;
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use jumperless_common::{frame::write_dump_entry, types::NetId, write_voltage, SupplySwitchPos};
+use jumperless_shell::{Executor, Instruction};
 
-use crate::nets::SupplySwitchPos;
-use crate::task::{net_manager, leds};
-use crate::{bus, task};
+use core::fmt::Write as _;
 
-enum Instruction {
-    Help,
-    Reset,
-    RainbowBounce,
-    SetSwitchPos(SupplySwitchPos),
-    PrintSwitchPos,
-    Clear,
-    AddBridge(Node, Node),
-    TestLed(usize),
-}
+use crate::task::{dac, measure, net_manager, leds, settings};
+use crate::{bus, bus::Reply, device_info, state, task};
 
-impl Instruction {
-    fn parse(input: &str) -> Result<Option<Instruction>, &'static [u8]> {
-        let mut tokens = input.trim().split_ascii_whitespace();
-        if let Some(token) = tokens.next() {
-            match token {
-                "help" => {
-                    no_more_args(&mut tokens)?;
-                    Ok(Some(Instruction::Help))
-                }
-                "reset" => {
-                    no_more_args(&mut tokens)?;
-                    Ok(Some(Instruction::Reset))
-                }
-                "rainbow-bounce" => {
-                    no_more_args(&mut tokens)?;
-                    Ok(Some(Instruction::RainbowBounce))
-                }
-                "switch-pos" => {
-                    if let Some(pos) = tokens.next() {
-                        no_more_args(&mut tokens)?;
-                        if let Some(pos) = SupplySwitchPos::parse(pos) {
-                            Ok(Some(Instruction::SetSwitchPos(pos)))
-                        } else {
-                            Err(b"Error: invalid argument\r\n")
-                        }
-                    } else {
-                        Ok(Some(Instruction::PrintSwitchPos))
-                    }
-                }
-                "clear" => {
-                    no_more_args(&mut tokens)?;
-                    Ok(Some(Instruction::Clear))
-                }
-                "add-bridge" => {
-                    let a = shift_arg(&mut tokens)?;
-                    let b = shift_arg(&mut tokens)?;
-                    no_more_args(&mut tokens)?;
-                    if let Ok(a) = a.parse::<Node>() {
-                        if let Ok(b) = b.parse::<Node>() {
-                            Ok(Some(Instruction::AddBridge(a, b)))
-                        } else {
-                            Err(b"Error: invalid second node\r\n")
-                        }
-                    } else {
-                        Err(b"Error: invalid  irstnode\r\n")
-                    }
-                }
-                "test-led" => {
-                    let i = shift_arg(&mut tokens)?;
-                    no_more_args(&mut tokens)?;
-                    if let Ok(i) = i.parse::<usize>() {
-                        Ok(Some(Instruction::TestLed(i)))
-                    } else {
-                        Err(b"Error: invalid led number\r\n")
-                    }
-                }
-                // "chipdump" => {
-                //     no_more_args(&mut tokens)?;
-                // }
-                _ => Err(b"Error: no such instruction\r\n"),
-            }
-        } else {
-            Ok(None)
-        }
-    }
-}
+/// Interval in which the line coding is checked for a 1200 baud touch
+const TOUCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
+/// Shell running on the USB serial port, executing instructions via the [`bus`]
+pub type Shell<'a, 'b, const BUF_SIZE: usize> = jumperless_shell::Shell<UsbSerial<'a, 'b>, BusExecutor, BUF_SIZE>;
+
+#[derive(Debug)]
 pub struct Disconnected {}
 
 impl From<EndpointError> for Disconnected {
@@ -97,210 +29,294 @@ impl From<EndpointError> for Disconnected {
     }
 }
 
-pub struct Overflow;
-
-impl From<Overflow> for &'static [u8] {
-    fn from(_: Overflow) -> Self {
-        "Input buffer overflow".as_bytes()
+impl embedded_io_async::Error for Disconnected {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::NotConnected
     }
 }
 
-pub struct Shell<'a, 'b, const BUF_SIZE: usize> {
+/// Makes a CDC ACM class usable via [`Read`] and [`Write`]
+///
+/// Reads return one packet at a time, so the read buffer must be at least as large as the max packet size.
+///
+/// While waiting for input, it watches for a "1200 baud touch" (the host opening the port at 1200 baud,
+/// and closing it again), which reboots into the USB bootloader. This is the convention used by
+/// Arduino-style tooling to start a firmware update.
+pub struct UsbSerial<'a, 'b> {
     class: &'a mut CdcAcmClass<'b, Driver<'b, USB>>,
-    buffer: LineBuffer<BUF_SIZE>,
+    // a full packet was written last, which needs to be terminated by a zero length packet on flush
+    needs_zlp: bool,
 }
 
-const HELP: &[&[u8]] = &[
-    b"Available instructions:\r\n",
-    b"  help                      Print this help text\r\n",
-    b"  reset                     Reset (reboot) the device\r\n",
-    b"  rainbow-bounce            Play rainbow animation\r\n",
-    b"  switch-pos [<5V|3V3|8V>]  Get/set switch position\r\n",
-    b"  clear                     Clear all connections\r\n",
-    b"  add-bridge <node> <node>  Connect two nodes\r\n",
-    b"  test-led <led-number>     Test an LED\r\n",
-];
-
-impl<'a, 'b, const BUF_SIZE: usize> Shell<'a, 'b, BUF_SIZE> {
+impl<'a, 'b> UsbSerial<'a, 'b> {
     pub fn new(class: &'a mut CdcAcmClass<'b, Driver<'b, USB>>) -> Self {
-        Self {
-            class,
-            buffer: LineBuffer::new(),
-        }
+        Self { class, needs_zlp: false }
     }
+}
 
-    /// Run the shell, until the connection is terminated
-    ///
-    /// Reads input, filling the input buffer, then parses
-    /// and executes instructions when ENTER ('\r') is pressed.
-    pub async fn run(&mut self) -> Result<(), Disconnected> {
-        let mut buf = [0; 64];
-        // did we see an escape byte?
-        let mut escape = false;
-        // was a control sequence introduced?
-        let mut csi = false;
-        loop {
-            let n = self.class.read_packet(&mut buf).await?;
+impl ErrorType for UsbSerial<'_, '_> {
+    type Error = Disconnected;
+}
 
-            let mut submit = false;
+impl UsbSerial<'_, '_> {
+    fn touched_1200_baud(&self) -> bool {
+        self.class.line_coding().data_rate() == 1200 && !self.class.dtr()
+    }
+}
 
-            for &c in &buf[..n] {
-                if csi {
-                    match c {
-                        b'C' => { // RIGHT
-                            self.buffer.move_right();
-                        }
-                        b'D' => { // LEFT
-                            self.buffer.move_left();
-                        }
-                        b'F' => { // END
-                            self.buffer.move_end();
-                        }
-                        b'H' => { // HOME
-                            self.buffer.move_home();
-                        }
-                        _ => {
-                            defmt::debug!("Unhandled CSI: {}", c);
-                        }
-                    }
-                    csi = false;
-                } else if escape {
-                    if c == b'[' {
-                        csi = true;
-                    } else {
-                        defmt::debug!("Unhandled escape: {}", c);
+impl Read for UsbSerial<'_, '_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Disconnected> {
+        loop {
+            match select(self.class.read_packet(buf), Timer::after(TOUCH_POLL_INTERVAL)).await {
+                Either::First(result) => return Ok(result?),
+                Either::Second(()) => {
+                    if self.touched_1200_baud() {
+                        defmt::info!("1200 baud touch, rebooting into bootloader");
+                        bus::inject(task::watchdog::Message::RebootToBootloader).await;
                     }
-                    escape = false;
-                } else {
-                    if c == b'\r' { // ENTER
-                        submit = true;
-                    } else if c == 27 { // ESC
-                        escape = true;
-                    } else if c == 3 { // Ctrl+C
-                        self.buffer.reset();
-                        self.class.write_packet(b"\r\n^C\r\n").await?;
-                    } else if c == 127 { // BACKSPACE
-                        self.buffer.backspace();
-                    } else if c.is_ascii_graphic() || c == b' ' {
-                        if let Err(_) = self.buffer.insert(c) {
-                            self.buffer.reset();
-                            self.class.write_packet(b"\r\n -- overflow; buffer cleared --\r\n").await?;
-                        }
-                    } else if c.is_ascii_control() {
-                        defmt::debug!("Unhandled control character: {}", c);
-                    };
                 }
             }
-
-            self.prompt().await?;
-
-            if submit {
-                self.class.write_packet(b"\r\n").await?;
-                self.process().await?;
-            }
         }
     }
+}
 
-    /// (Re-) print the prompt, including the input buffer
-    async fn prompt(&mut self) -> Result<(), Disconnected> {
-        // print prompt at beginning of line
-        self.class.write_packet(b"\r> ").await?;
-
-        // print current input buffer
-        let line = self.buffer.content();
-        if line.len() > 0 {
-            self.class
-                .write_packet(line)
-                .await?;
-        }
-        // overwrite one more character after the input, to deal with backspace
-        self.class.write_packet(&[b' ', 8]).await?;
-
-        // move cursor to correct position
-        let cursor = self.buffer.cursor() + 2;
-        self.class.write_packet(&[b'\r']).await?;
-        for _ in 0..cursor {
-            self.class.write_packet(&[27, b'[', b'C']).await?;
-        }
-        Ok(())
+impl Write for UsbSerial<'_, '_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Disconnected> {
+        let max_packet_size = self.class.max_packet_size() as usize;
+        let n = buf.len().min(max_packet_size);
+        self.class.write_packet(&buf[..n]).await?;
+        self.needs_zlp = n == max_packet_size;
+        Ok(n)
     }
 
-    /// Process the input buffer, and execute any instruction found
-    async fn process(&mut self) -> Result<(), Disconnected> {
-        let buffer = self.buffer.content();
-        if buffer.is_empty() {
-            self.prompt().await?;
-            return Ok(());
-        }
-        if let Ok(input) = core::str::from_utf8(buffer) {
-            match Instruction::parse(input) {
-                Ok(Some(instruction)) => self.execute(instruction).await?,
-                Ok(None) => {}
-                Err(message) => self.class.write_packet(message).await?,
-            }
+    async fn flush(&mut self) -> Result<(), Disconnected> {
+        if self.needs_zlp {
+            self.class.write_packet(&[]).await?;
+            self.needs_zlp = false;
         }
-        self.buffer.reset();
-        self.prompt().await?;
         Ok(())
     }
+}
+
+/// Executes shell instructions, by sending messages to the other tasks
+pub struct BusExecutor;
 
-    /// Execute an instruction
-    async fn execute(&mut self, instruction: Instruction) -> Result<(), Disconnected> {
+impl Executor for BusExecutor {
+    async fn execute<W: Read + Write>(&mut self, instruction: Instruction, output: &mut W) -> Result<(), W::Error> {
         match instruction {
-            Instruction::Help => {
-                for line in HELP {
-                    self.class.write_packet(line).await?;
-                }
-                Ok(())
+            // handled by the shell itself
+            Instruction::Help | Instruction::Machine(_) => Ok(()),
+            Instruction::Info => {
+                let mut info: heapless::String<128> = heapless::String::new();
+                _ = write!(
+                    info,
+                    "board: {}\r\nfirmware: {} ({})\r\nserial: {}\r\n",
+                    device_info::BOARD_REVISION,
+                    device_info::FIRMWARE_VERSION,
+                    device_info::GIT_HASH,
+                    device_info::serial_number().await,
+                );
+                output.write_all(info.as_bytes()).await
             }
             Instruction::Reset => {
                 bus::inject(task::watchdog::Message::Reset).await;
                 Ok(())
             }
+            Instruction::Bootloader => {
+                output.write_all(b"Rebooting into bootloader\r\n").await?;
+                output.flush().await?;
+                bus::inject(task::watchdog::Message::RebootToBootloader).await;
+                Ok(())
+            }
             Instruction::RainbowBounce => {
                 bus::inject(task::leds::Message::PlayRainbowBounce).await;
                 Ok(())
             }
             Instruction::SetSwitchPos(pos) => {
-                if let Some(nets) = crate::NETS.lock().await.as_mut() {
-                    nets.supply_switch_pos = pos;
-                    bus::inject(task::leds::Message::UpdateFromNets).await;
-                }
+                state::lock().await.set_rails(pos.into());
                 Ok(())
             }
             Instruction::PrintSwitchPos => {
-                if let Some(nets) = crate::NETS.lock().await.as_ref() {
-                    self.class.write_packet(nets.supply_switch_pos.label().as_bytes()).await?;
-                    self.class.write_packet(b"\r\n").await?;
+                let rails = state::lock().await.rails();
+                let mut line: heapless::String<48> = heapless::String::new();
+                // the rails may not match any switch position, once they are configured otherwise
+                _ = match SupplySwitchPos::from_rails(&rails) {
+                    Some(pos) => write!(line, "{}\r\n", pos.label()),
+                    None => write!(line, "custom ({})\r\n", rails),
+                };
+                output.write_all(line.as_bytes()).await
+            }
+            Instruction::Clear => update_nets(net_manager::Message::Reset, output).await,
+            Instruction::AddBridge(a, b) => {
+                update_nets(|reply| net_manager::Message::AddBridge(a, b, reply), output).await
+            }
+            Instruction::TestLed(index) => {
+                bus::inject(leds::Message::TestLed(index)).await;
+                Ok(())
+            }
+            Instruction::DumpLeds => {
+                let frame = leds::FRAME.lock().await.clone();
+                // written in chunks which fit into a single packet
+                let mut chunk: heapless::String<64> = heapless::String::new();
+                for i in 0..leds::NUM_LEDS {
+                    if chunk.len() + 7 > chunk.capacity() {
+                        output.write_all(chunk.as_bytes()).await?;
+                        chunk.clear();
+                    }
+                    _ = write_dump_entry(&mut chunk, i, frame.rgb8(i));
                 }
+                output.write_all(chunk.as_bytes()).await?;
+                output.write_all(b"\r\n").await
+            }
+            Instruction::SetBrightness(brightness) => {
+                state::lock().await.settings_mut().brightness = brightness;
                 Ok(())
             }
-            Instruction::Clear => {
-                bus::inject(net_manager::Message::Reset).await;
+            Instruction::PrintBrightness => {
+                let mut line: heapless::String<8> = heapless::String::new();
+                _ = write!(line, "{}%\r\n", state::lock().await.settings().brightness);
+                output.write_all(line.as_bytes()).await
+            }
+            Instruction::SetPowerBudget(budget) => {
+                state::lock().await.settings_mut().power_budget = budget;
                 Ok(())
             }
-            Instruction::AddBridge(a, b) => {
-                bus::inject(net_manager::Message::AddBridge(a, b)).await;
+            Instruction::PrintPowerBudget => {
+                let mut line: heapless::String<12> = heapless::String::new();
+                _ = write!(line, "{}mA\r\n", state::lock().await.settings().power_budget);
+                output.write_all(line.as_bytes()).await
+            }
+            Instruction::Highlight(node) => {
+                let nodes = {
+                    let state = state::lock().await;
+                    let nets = state.nets();
+                    // nodes which are not part of any net are highlighted on their own
+                    match nets.with_node(node) {
+                        Some(net_id) => nets.nets[net_id.index()].nodes,
+                        None => [node].into_iter().collect(),
+                    }
+                };
+                bus::inject(leds::Message::Highlight(nodes)).await;
                 Ok(())
             }
-            Instruction::TestLed(index) => {
-                bus::inject(leds::Message::TestLed(index)).await;
+            Instruction::HighlightNet(id) => {
+                let nodes = match state::lock().await.nets().nets.get(NetId::from(id).index()) {
+                    Some(net) if !net.nodes.is_empty() => Some(net.nodes),
+                    _ => None,
+                };
+                match nodes {
+                    Some(nodes) => bus::inject(leds::Message::Highlight(nodes)).await,
+                    None => output.write_all(b"Error: no such net\r\n").await?,
+                }
+                Ok(())
+            }
+            Instruction::ClearHighlights => {
+                bus::inject(leds::Message::ClearHighlights).await;
+                Ok(())
+            }
+            Instruction::SetColor(id, color) => {
+                let message = |reply| net_manager::Message::SetColor(NetId::from(id), color, reply);
+                if !bus::request(&net_manager::COLOR_REPLY, message).await {
+                    output.write_all(b"Error: no such net\r\n").await?;
+                }
+                Ok(())
+            }
+            Instruction::SetPalette(palette) => {
+                state::lock().await.settings_mut().palette = palette;
+                bus::inject(net_manager::Message::Recolor).await;
                 Ok(())
             }
+            Instruction::PrintPalette => {
+                let palette = state::lock().await.settings().palette;
+                output.write_all(palette.label().as_bytes()).await?;
+                output.write_all(b"\r\n").await
+            }
+            Instruction::PrintLastCrash => match settings::last_crash().await {
+                Some(crash) => {
+                    output.write_all(crash.kind.label().as_bytes()).await?;
+                    if !crash.message.is_empty() {
+                        output.write_all(b": ").await?;
+                        output.write_all(crash.message.as_bytes()).await?;
+                    }
+                    output.write_all(b"\r\n").await
+                }
+                None => output.write_all(b"No crash recorded\r\n").await,
+            },
+            Instruction::ClearLastCrash => {
+                bus::inject(settings::Message::ClearCrashLog).await;
+                Ok(())
+            }
+            Instruction::SetDac(channel, dac_output) => {
+                let result = bus::request(&dac::REPLY, |reply| dac::Message::Set(channel, dac_output, reply)).await;
+                if result.is_err() {
+                    output.write_all(b"Error: DAC not responding\r\n").await?;
+                }
+                Ok(())
+            }
+            Instruction::MeasureVoltage(node) => {
+                let reading = bus::request(&measure::VOLTAGE_REPLY, |reply| measure::Message::ReadVoltage(node, reply)).await;
+                let mut line: heapless::String<64> = heapless::String::new();
+                match reading {
+                    Ok(voltage) => {
+                        _ = write!(line, "{}: ", node.as_str());
+                        _ = write_voltage(&mut line, voltage);
+                        _ = line.push_str("\r\n");
+                    }
+                    Err(err) => _ = write!(line, "Error: {}\r\n", err),
+                }
+                output.write_all(line.as_bytes()).await
+            }
+            Instruction::MeasureCurrent => {
+                match bus::request(&measure::CURRENT_REPLY, measure::Message::ReadCurrent).await {
+                    Ok(reading) => {
+                        let mut line: heapless::String<64> = heapless::String::new();
+                        _ = write!(line, "{}\r\n", reading);
+                        output.write_all(line.as_bytes()).await
+                    }
+                    Err(_) => output.write_all(b"Error: current sensor not responding\r\n").await,
+                }
+            }
+            Instruction::LogCurrent(interval) => {
+                output.write_all(b"time_ms,current_ua,bus_voltage_mv\r\n").await?;
+                let start = Instant::now();
+                let mut ticker = Ticker::every(Duration::from_millis(interval as u64));
+                let mut buf = [0; 64];
+                loop {
+                    // stop on any input
+                    if let Either::First(result) = select(output.read(&mut buf), ticker.next()).await {
+                        result?;
+                        return Ok(());
+                    }
+                    let reading = match bus::request(&measure::CURRENT_REPLY, measure::Message::ReadCurrent).await {
+                        Ok(reading) => reading,
+                        Err(_) => return output.write_all(b"Error: current sensor not responding\r\n").await,
+                    };
+                    let mut line: heapless::String<48> = heapless::String::new();
+                    _ = write!(
+                        line,
+                        "{},{},{}\r\n",
+                        start.elapsed().as_millis(),
+                        reading.current,
+                        reading.bus_voltage
+                    );
+                    output.write_all(line.as_bytes()).await?;
+                }
+            }
         }
     }
 }
 
-fn shift_arg<'a, T: Iterator<Item = &'a str>>(tokens: &mut T) -> Result<&'a str, &'static [u8]> {
-    match tokens.next() {
-        Some(arg) => Ok(arg),
-        None => Err(b"Error: missing argument\r\n"),
-    }
-}
-
-fn no_more_args<'a, T: Iterator<Item = &'a str>>(tokens: &mut T) -> Result<(), &'static [u8]> {
-    match tokens.next() {
-        Some(_) => Err(b"Error: unexpected extra arguments\r\n"),
-        None => Ok(()),
+/// Send a request to the net manager, and print whether the resulting nets could be routed
+async fn update_nets<W: Write>(
+    message: impl FnOnce(Reply<net_manager::RoutingResult>) -> net_manager::Message,
+    output: &mut W,
+) -> Result<(), W::Error> {
+    match bus::request(&net_manager::REPLY, message).await {
+        Ok(()) => output.write_all(b"Routed OK\r\n").await,
+        Err(err) => {
+            let mut line: heapless::String<64> = heapless::String::new();
+            _ = write!(line, "Error: {}\r\n", err);
+            output.write_all(line.as_bytes()).await
+        }
     }
 }
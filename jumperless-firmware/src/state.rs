@@ -0,0 +1,110 @@
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    mutex::{Mutex, MutexGuard},
+};
+use jumperless_common::{Rails, SupplySwitchPos};
+
+use crate::bus::{self, Event};
+use crate::nets::Nets;
+use crate::settings::Settings;
+
+/// Application state, shared between tasks
+pub struct State {
+    /// The netlist, including the color of each net
+    nets: Nets,
+    /// Voltages of the power rails
+    rails: Rails,
+    /// LED and device settings, persisted by `task::settings`
+    settings: Settings,
+}
+
+static STATE: Mutex<ThreadModeRawMutex, Option<State>> = Mutex::new(None);
+
+/// Initialize the state. Must be called once during startup, before any task accesses the state.
+pub async fn init(settings: Settings) {
+    *STATE.lock().await = Some(State {
+        nets: Nets::default(),
+        rails: SupplySwitchPos::_5V.into(),
+        settings,
+    });
+}
+
+/// Lock the state, for reading or modifying it
+///
+/// Changes are announced when the returned guard is dropped, by publishing an [`Event`] for each
+/// part of the state that was modified (accessed mutably).
+///
+/// The guard should not be held across long `await`s (like [`bus::inject`]), since that blocks all
+/// other tasks which access the state.
+pub async fn lock() -> StateGuard {
+    StateGuard {
+        guard: STATE.lock().await,
+        nets_changed: false,
+        rails_changed: false,
+        settings_changed: false,
+    }
+}
+
+/// Access to the [`State`], see [`lock`]
+pub struct StateGuard {
+    guard: MutexGuard<'static, ThreadModeRawMutex, Option<State>>,
+    nets_changed: bool,
+    rails_changed: bool,
+    settings_changed: bool,
+}
+
+impl StateGuard {
+    fn state(&self) -> &State {
+        self.guard.as_ref().expect("state not initialized")
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        self.guard.as_mut().expect("state not initialized")
+    }
+
+    pub fn nets(&self) -> &Nets {
+        &self.state().nets
+    }
+
+    /// Modify the nets. Publishes [`Event::NetsChanged`].
+    ///
+    /// Only used by the net manager. Other tasks ask it to change the nets (see [`crate::task::net_manager::Message`]).
+    pub fn nets_mut(&mut self) -> &mut Nets {
+        self.nets_changed = true;
+        &mut self.state_mut().nets
+    }
+
+    pub fn rails(&self) -> Rails {
+        self.state().rails
+    }
+
+    /// Change the rail voltages. Publishes [`Event::RailsChanged`].
+    pub fn set_rails(&mut self, rails: Rails) {
+        self.rails_changed = true;
+        self.state_mut().rails = rails;
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.state().settings
+    }
+
+    /// Modify the settings. Publishes [`Event::SettingsChanged`].
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        self.settings_changed = true;
+        &mut self.state_mut().settings
+    }
+}
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        if self.nets_changed {
+            bus::publish(Event::NetsChanged);
+        }
+        if self.rails_changed {
+            bus::publish(Event::RailsChanged);
+        }
+        if self.settings_changed {
+            bus::publish(Event::SettingsChanged);
+        }
+    }
+}
@@ -4,7 +4,19 @@ pub mod leds;
 
 /// Watchdog task
 ///
-/// Periodically nudges the watchdog to keep alive. If this task isn't scheduled for more than 1.5 seconds, the device resets.
+/// Periodically nudges the watchdog to keep alive, as long as all supervised tasks check in regularly.
+/// If this task isn't scheduled for more than 1.5 seconds, or a supervised task stalls, the device resets.
 pub mod watchdog;
 
 pub mod net_manager;
+
+/// Drives the DAC outputs (`DAC0` / `DAC1` nodes), with constant voltages or waveforms
+pub mod dac;
+
+/// Measurements, using the current sensor and the ADC
+pub mod measure;
+
+/// Persistent settings
+///
+/// Owns the flash sectors holding the settings and the crash log, and writes the settings whenever they change.
+pub mod settings;
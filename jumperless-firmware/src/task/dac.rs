@@ -0,0 +1,94 @@
+use crate::bus::{self, Reply, ReplySlot};
+use crate::task::watchdog::{Heartbeat, TaskId};
+use crate::{I2cError, SharedI2c};
+use embassy_futures::select::{select, Either};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    channel::{Channel, Sender},
+};
+use embassy_time::{Duration, Ticker};
+use jumperless_common::dac::{DacChannel, DacOutput, Mcp4728, Phase, Wave};
+
+static CHANNEL: bus::Channel<Message> = Channel::new();
+
+/// Replies to [`Message::Set`]
+pub static REPLY: ReplySlot<Result<(), I2cError>> = ReplySlot::new();
+
+/// Rate at which waves are sampled (in Hz)
+const SAMPLE_RATE: u32 = 1000;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_hz(SAMPLE_RATE as u64);
+
+pub type Dac = Mcp4728<SharedI2c>;
+
+/// A [`bus::BusMessage`] targeting the `dac` task.
+pub enum Message {
+    /// Change what a DAC output produces
+    ///
+    /// Replies once the (first) voltage was written, or writing failed.
+    Set(DacChannel, DacOutput, Reply<Result<(), I2cError>>),
+}
+
+impl bus::BusMessage for Message {
+    fn sender<'a>() -> Sender<'a, ThreadModeRawMutex, Self, { bus::CHANNEL_SIZE }> {
+        CHANNEL.sender()
+    }
+}
+
+#[embassy_executor::task]
+pub async fn main(mut dac: Dac) {
+    let mut heartbeat = Heartbeat::new(TaskId::Dac);
+    let mut ticker = Ticker::every(SAMPLE_INTERVAL);
+    // waves currently playing on each channel, with their phase
+    let mut waves: [Option<(Wave, Phase)>; 2] = [None; 2];
+
+    loop {
+        let message = if waves.iter().any(Option::is_some) {
+            heartbeat.beat().await;
+            match select(CHANNEL.receive(), ticker.next()).await {
+                Either::First(message) => Some(message),
+                Either::Second(()) => None,
+            }
+        } else {
+            Some(heartbeat.receive(&CHANNEL).await)
+        };
+
+        match message {
+            Some(Message::Set(channel, output, reply)) => {
+                let voltage = match output {
+                    DacOutput::Voltage(voltage) => {
+                        waves[channel.index()] = None;
+                        voltage
+                    }
+                    DacOutput::Wave(wave) => {
+                        if waves.iter().all(Option::is_none) {
+                            // don't catch up on the ticks missed while idle
+                            ticker.reset();
+                        }
+                        waves[channel.index()] = Some((wave, 0));
+                        wave.sample(0)
+                    }
+                };
+                let result = dac.set_voltage(channel, voltage).await;
+                if let Err(e) = result {
+                    defmt::error!("Failed to set DAC{}: {}", channel.index(), e);
+                    waves[channel.index()] = None;
+                }
+                reply.send(result);
+            }
+            // next sample is due
+            None => {
+                for channel in DacChannel::ALL {
+                    let Some((wave, phase)) = &mut waves[channel.index()] else {
+                        continue;
+                    };
+                    *phase = phase.wrapping_add(wave.phase_step(SAMPLE_RATE));
+                    if let Err(e) = dac.set_voltage(channel, wave.sample(*phase)).await {
+                        defmt::error!("Failed to set DAC{}, stopping wave: {}", channel.index(), e);
+                        waves[channel.index()] = None;
+                    }
+                }
+            }
+        }
+    }
+}
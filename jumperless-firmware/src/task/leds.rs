@@ -1,28 +1,40 @@
-use crate::{bus, NETS};
+use crate::animation::{Animator, Effect, Overlay, FRAME_DURATION};
+use crate::leds::Frame;
+use crate::task::watchdog::{Heartbeat, TaskId};
+use crate::{bus, bus::Event, state};
+use embassy_futures::select::{select3, Either3};
 use embassy_rp::peripherals::PIO0;
 use embassy_sync::{
     blocking_mutex::raw::ThreadModeRawMutex,
     channel::{Channel, Sender},
+    mutex::Mutex,
 };
-use embassy_time::{Duration, Timer};
+use embassy_time::Ticker;
+use jumperless_common::{board::Node, types::set::NodeSet};
 
-/// Number of LEDs on the board. This will vary in the future, depending on hardware revision.
-const NUM_LEDS: usize = 111;
+/// Number of LEDs on the board
+pub const NUM_LEDS: usize = jumperless_common::board::leds::COUNT;
 
 type Leds = crate::leds::Leds<'static, PIO0, 0, NUM_LEDS>;
 
 static CHANNEL: bus::Channel<Message> = Channel::new();
 
+/// The frame which was written to the LEDs most recently (before applying brightness)
+pub static FRAME: Mutex<ThreadModeRawMutex, Frame<NUM_LEDS>> = Mutex::new(Frame::new());
+
 /// A [`bus::BusMessage`] targeting the `leds` task.
 pub enum Message {
     /// Play the "rainbow-bounce" animation, then return to normal state
     PlayRainbowBounce,
 
-    /// Update LEDs to reflect changes to the nets or other state
-    UpdateFromNets,
-
     /// Turn on a single LED for testing, for half a second
     TestLed(usize),
+
+    /// Blink the LEDs of the given nodes for a few seconds, then keep them brighter until [`Message::ClearHighlights`]
+    Highlight(NodeSet<Node>),
+
+    /// Stop highlighting all nodes
+    ClearHighlights,
 }
 
 impl bus::BusMessage for Message {
@@ -33,45 +45,87 @@ impl bus::BusMessage for Message {
 
 #[embassy_executor::task]
 pub async fn main(mut leds: Leds) {
-    // Play startup animation
-    startup_leds(&mut leds).await;
+    let mut heartbeat = Heartbeat::new(TaskId::Leds);
 
-    // Set up normal state (colors indicate nets)
-    update_from_nets(&mut leds).await;
+    if NUM_LEDS == 0 {
+        // the board spec has no LED mapping (see `boardspec/README.md`)
+        defmt::warn!("No LED mapping for this board, LEDs stay off");
+        loop {
+            heartbeat.receive(&CHANNEL).await;
+        }
+    }
+
+    let mut events = bus::subscribe();
+    let mut animator: Animator<NUM_LEDS> = Animator::new();
+    let mut ticker = Ticker::every(FRAME_DURATION);
+
+    // Set up normal state (colors indicate nets), which shows once the startup animation is done
+    update_from_nets(&mut animator).await;
+    animator.start(Effect::Startup);
 
     loop {
-        match CHANNEL.receive().await {
-            Message::PlayRainbowBounce => {
-                leds.rainbow_bounce(Duration::from_millis(40)).await;
-                // restore normal state
-                update_from_nets(&mut leds).await;
-            }
-            Message::UpdateFromNets => {
-                defmt::debug!("Updating from nets");
-                update_from_nets(&mut leds).await;
+        heartbeat.beat().await;
+
+        if animator.needs_render() {
+            let mut frame = FRAME.lock().await;
+            animator.render(&mut frame);
+            leds.flush(&frame).await;
+        }
+
+        if animator.is_animating() {
+            // handle messages and events until the next frame is due
+            loop {
+                match select3(CHANNEL.receive(), events.next_message_pure(), ticker.next()).await {
+                    Either3::First(message) => handle_message(message, &mut animator).await,
+                    Either3::Second(event) => handle_event(event, &mut animator, &mut leds).await,
+                    Either3::Third(()) => break,
+                }
             }
-            Message::TestLed(i) => {
-                leds.off().await;
-                Timer::after_millis(1).await;
-                leds.set_rgb8(i, (32, 32, 32));
-                leds.flush().await;
-                Timer::after_millis(500).await;
-                update_from_nets(&mut leds).await;
+        } else {
+            match select3(CHANNEL.receive(), events.next_message_pure(), heartbeat.due()).await {
+                Either3::First(message) => handle_message(message, &mut animator).await,
+                Either3::Second(event) => handle_event(event, &mut animator, &mut leds).await,
+                // time to check in with the watchdog
+                Either3::Third(()) => continue,
             }
+            // in case an animation was started, it continues one frame duration from now
+            ticker.reset();
         }
     }
 }
 
-async fn startup_leds(leds: &mut Leds) {
-    leds.startup_colors().await;
-    Timer::after_millis(2).await;
-    leds.set_rgb8(110, (32, 0, 0));
-    leds.flush().await;
+async fn handle_message(message: Message, animator: &mut Animator<NUM_LEDS>) {
+    match message {
+        Message::PlayRainbowBounce => animator.start(Effect::RainbowBounce),
+        Message::TestLed(i) => animator.start(Effect::TestLed(i)),
+        Message::Highlight(nodes) => {
+            animator.add_overlay(Overlay::Highlight(nodes));
+            animator.start(Effect::Highlight(nodes));
+        }
+        Message::ClearHighlights => animator.clear_overlays(),
+    }
 }
 
-async fn update_from_nets(leds: &mut Leds) {
-    Timer::after_millis(2).await;
-    if let Some(nets) = NETS.lock().await.as_ref() {
-        leds.update_from_nets(nets).await;
+async fn handle_event(event: Event, animator: &mut Animator<NUM_LEDS>, leds: &mut Leds) {
+    match event {
+        Event::NetsChanged | Event::RailsChanged => {
+            defmt::debug!("Updating from nets");
+            update_from_nets(animator).await;
+        }
+        // flash the nodes which could not be connected
+        Event::RoutingFailed(nodes) => animator.start(Effect::Error(nodes)),
+        Event::SettingsChanged => {
+            let settings = state::lock().await.settings();
+            leds.set_brightness(settings.brightness);
+            leds.set_power_budget(settings.power_budget as u32);
+            // marks the frame as changed, so it is flushed again
+            animator.base_mut();
+        }
+        Event::UsbConnected | Event::UsbDisconnected => {}
     }
 }
+
+async fn update_from_nets(animator: &mut Animator<NUM_LEDS>) {
+    let state = state::lock().await;
+    animator.base_mut().update_from_nets(state.nets(), &state.rails());
+}
@@ -0,0 +1,109 @@
+use crate::bus::{self, Reply, ReplySlot};
+use crate::task::net_manager;
+use crate::task::watchdog::{Heartbeat, TaskId};
+use crate::{I2cError, SharedI2c};
+use embassy_rp::adc::{self, Adc};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    channel::{Channel, Sender},
+};
+use embassy_time::{Duration, Timer};
+use jumperless_common::{
+    board::Node,
+    current_sense::{CurrentReading, Ina219},
+    probe::ProbeError,
+    Millivolts,
+};
+
+static CHANNEL: bus::Channel<Message> = Channel::new();
+
+/// Replies to [`Message::ReadCurrent`]
+pub static CURRENT_REPLY: ReplySlot<Result<CurrentReading, I2cError>> = ReplySlot::new();
+
+/// Replies to [`Message::ReadVoltage`]
+pub static VOLTAGE_REPLY: ReplySlot<Result<Millivolts, VoltageError>> = ReplySlot::new();
+
+/// Time to wait after connecting a probe, before sampling
+const SETTLE_TIME: Duration = Duration::from_millis(2);
+
+/// Number of ADC samples averaged for a voltage reading
+const SAMPLES: u32 = 16;
+
+/// A [`bus::BusMessage`] targeting the `measure` task.
+pub enum Message {
+    /// Read the current sensor
+    ReadCurrent(Reply<Result<CurrentReading, I2cError>>),
+    /// Measure the voltage of a node, by connecting it to a free ADC for a moment
+    ReadVoltage(Node, Reply<Result<Millivolts, VoltageError>>),
+}
+
+impl bus::BusMessage for Message {
+    fn sender<'a>() -> Sender<'a, ThreadModeRawMutex, Self, { bus::CHANNEL_SIZE }> {
+        CHANNEL.sender()
+    }
+}
+
+/// Reasons why measuring a voltage failed
+pub enum VoltageError {
+    Probe(ProbeError),
+    Adc(adc::Error),
+}
+
+impl core::fmt::Display for VoltageError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VoltageError::Probe(err) => write!(f, "{}", err),
+            VoltageError::Adc(_) => write!(f, "ADC conversion failed"),
+        }
+    }
+}
+
+/// ADC and its inputs (on GPIO 26 to 29), connected to the `ADC0` to `ADC3` nodes
+pub struct Adcs {
+    pub adc: Adc<'static, adc::Async>,
+    pub channels: [adc::Channel<'static>; 4],
+}
+
+#[embassy_executor::task]
+pub async fn main(mut current_sensor: Ina219<SharedI2c>, mut adcs: Adcs) {
+    let mut heartbeat = Heartbeat::new(TaskId::Measure);
+    let mut initialized = false;
+    loop {
+        match heartbeat.receive(&CHANNEL).await {
+            Message::ReadCurrent(reply) => {
+                // configured on first use (retried until the sensor responds)
+                if !initialized {
+                    match current_sensor.init().await {
+                        Ok(()) => initialized = true,
+                        Err(e) => defmt::error!("Failed to initialize current sensor: {}", e),
+                    }
+                }
+                reply.send(current_sensor.read().await);
+            }
+            Message::ReadVoltage(node, reply) => {
+                let probe = |reply| net_manager::Message::ConnectProbe(node, reply);
+                let channel = match bus::request(&net_manager::PROBE_REPLY, probe).await {
+                    Ok(channel) => channel,
+                    Err(err) => {
+                        reply.send(Err(VoltageError::Probe(err)));
+                        continue;
+                    }
+                };
+                Timer::after(SETTLE_TIME).await;
+                let mut sum = 0;
+                let mut result = Ok(());
+                for _ in 0..SAMPLES {
+                    match adcs.adc.read(&mut adcs.channels[channel.index()]).await {
+                        Ok(sample) => sum += sample as u32,
+                        Err(err) => {
+                            result = Err(VoltageError::Adc(err));
+                            break;
+                        }
+                    }
+                }
+                bus::inject(net_manager::Message::DisconnectProbe).await;
+                reply.send(result.map(|()| channel.voltage((sum / SAMPLES) as u16)));
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{bus, nets::Nets, NETS, ch446q::Ch446q, task::leds};
+use crate::task::watchdog::{Heartbeat, TaskId};
+use crate::{bus, bus::{Event, Reply, ReplySlot}, nets::{Nets, MAX_NETS}, state, ch446q::{Ch446q, Packet}};
 use embassy_rp::peripherals::PIO1;
 use embassy_sync::{
     blocking_mutex::raw::ThreadModeRawMutex,
@@ -6,18 +7,49 @@ use embassy_sync::{
 };
 use embassy_time::Timer;
 use jumperless_common::{
-    nets_to_connections,
+    nets_to_connections::{self, nets_to_connections},
     board::{init_board, Board, Node},
-    ChipStatus,
+    color::{pick_net_color, Color, Palette},
+    probe::{added_crosspoints, route_probe, AdcChannel, ProbeError},
+    types::NetId,
+    ChipStatus, Crosspoint,
 };
 use rand::{Rng, SeedableRng};
 use rand::rngs::SmallRng;
 
 static CHANNEL: bus::Channel<Message> = Channel::new();
 
+/// Replies to [`Message::Reset`] and [`Message::AddBridge`]
+pub static REPLY: ReplySlot<RoutingResult> = ReplySlot::new();
+
+/// Replies to [`Message::SetColor`], with whether the net exists
+pub static COLOR_REPLY: ReplySlot<bool> = ReplySlot::new();
+
+/// Replies to [`Message::ConnectProbe`]
+pub static PROBE_REPLY: ReplySlot<Result<AdcChannel, ProbeError>> = ReplySlot::new();
+
+/// Outcome of applying changed nets to the chips
+pub type RoutingResult = Result<(), nets_to_connections::Error>;
+
+/// A [`bus::BusMessage`] targeting the `net_manager` task.
+///
+/// The net manager is the only task modifying the nets, so changes made while it routes can't get lost.
 pub enum Message {
-    Reset,
-    AddBridge(Node, Node),
+    /// Remove all connections
+    Reset(Reply<RoutingResult>),
+    /// Connect two nodes
+    AddBridge(Node, Node, Reply<RoutingResult>),
+    /// Pick new colors for all (non-special) nets, from the current palette
+    Recolor,
+    /// Set the color of a net, replying whether the net exists
+    SetColor(NetId, Color, Reply<bool>),
+    /// Temporarily connect a node to a free ADC, without changing existing connections (see [`route_probe`])
+    ///
+    /// Replies with the chosen ADC. The probe stays connected until [`Message::DisconnectProbe`],
+    /// or until the nets change.
+    ConnectProbe(Node, Reply<Result<AdcChannel, ProbeError>>),
+    /// Remove the probe connected by [`Message::ConnectProbe`]
+    DisconnectProbe,
 }
 
 impl bus::BusMessage for Message {
@@ -31,25 +63,78 @@ pub async fn main(mut chips: Ch446q<'static, PIO1, 0>) {
     let board = init_board();
     let mut chip_status = ChipStatus::default();
     let mut rng = rand::rngs::SmallRng::seed_from_u64(0);
+    let mut heartbeat = Heartbeat::new(TaskId::NetManager);
+    // connections including the probe, while one is connected
+    let mut probe: Option<ChipStatus> = None;
     loop {
-        match CHANNEL.receive().await {
-            Message::Reset => {
-                if let Some(nets) = NETS.lock().await.as_mut() {
-                    *nets = Nets::default();
-                    update_chips(nets, &mut chip_status, &mut chips, &board).await;
+        match heartbeat.receive(&CHANNEL).await {
+            Message::Reset(reply) => {
+                let result = apply_nets(Nets::default(), &mut chip_status, &mut chips, &board).await;
+                if result.is_ok() {
+                    // the chips were reset, which removed the probe
+                    probe = None;
+                }
+                reply.send(result);
+            }
+            Message::AddBridge(a, b, reply) => {
+                let (mut nets, palette) = {
+                    let state = state::lock().await;
+                    (state.nets().clone(), state.settings().palette)
+                };
+                add_bridge(&mut nets, a, b, palette, &mut rng);
+                let result = apply_nets(nets, &mut chip_status, &mut chips, &board).await;
+                if result.is_ok() {
+                    probe = None;
+                }
+                reply.send(result);
+            }
+            Message::Recolor => {
+                let mut state = state::lock().await;
+                let palette = state.settings().palette;
+                let nets = state.nets_mut();
+                let net_ids: heapless::Vec<NetId, MAX_NETS> = nets
+                    .nets
+                    .iter()
+                    .filter(|net| !net.id.is_special() && !net.nodes.is_empty())
+                    .map(|net| net.id)
+                    .collect();
+                for net_id in net_ids {
+                    let color = net_color(nets, net_id, palette, &mut rng);
+                    nets.set_color(net_id, color);
+                }
+            }
+            Message::SetColor(net_id, color, reply) => {
+                let mut state = state::lock().await;
+                let found = matches!(state.nets().nets.get(net_id.index()), Some(net) if !net.nodes.is_empty());
+                if found {
+                    state.nets_mut().set_color(net_id, color);
+                }
+                reply.send(found);
+            }
+            Message::ConnectProbe(node, reply) => {
+                if let Some(previous) = probe.take() {
+                    write_crosspoints(&mut chips, added_crosspoints(&chip_status, &previous), false).await;
+                }
+                let routed = route_probe(&state::lock().await.nets().nets, &chip_status, node, &board);
+                match routed {
+                    Ok((adc, status)) => {
+                        write_crosspoints(&mut chips, added_crosspoints(&chip_status, &status), true).await;
+                        probe = Some(status);
+                        reply.send(Ok(adc));
+                    }
+                    Err(err) => reply.send(Err(err)),
                 }
             }
-            Message::AddBridge(a, b) => {
-                if let Some(nets) = NETS.lock().await.as_mut() {
-                    add_bridge(nets, a, b, &mut rng);
-                    update_chips(nets, &mut chip_status, &mut chips, &board).await;
+            Message::DisconnectProbe => {
+                if let Some(status) = probe.take() {
+                    write_crosspoints(&mut chips, added_crosspoints(&chip_status, &status), false).await;
                 }
             }
         }
     }
 }
 
-fn add_bridge(nets: &mut Nets, a: Node, b: Node, rng: &mut SmallRng) {
+fn add_bridge(nets: &mut Nets, a: Node, b: Node, palette: Palette, rng: &mut SmallRng) {
     let net_a = nets.with_node(a);
     let net_b = nets.with_node(b);
     match (net_a, net_b) {
@@ -69,37 +154,82 @@ fn add_bridge(nets: &mut Nets, a: Node, b: Node, rng: &mut SmallRng) {
             _ = nets.add_node(net_b, a);
         },
         (None, None) => {
-            let net_id = nets.new_net(random_color(rng));
+            let net_id = nets.new_net((0, 0, 0));
             _ = nets.add_node(net_id, a);
             _ = nets.add_node(net_id, b);
+            // picked once the nodes are known, since the color depends on the neighboring nets
+            let color = net_color(nets, net_id, palette, rng);
+            nets.set_color(net_id, color);
         }
     }
 }
 
-async fn update_chips(nets: &Nets, chip_status: &mut ChipStatus, chips: &mut Ch446q<'static, PIO1, 0>, board: &Board) {
+/// Pick a color for the given net from the palette (taking other nets into account), or a random one
+fn net_color(nets: &Nets, net_id: NetId, palette: Palette, rng: &mut SmallRng) -> Color {
+    pick_net_color(palette, &nets.nets, &nets.colors, net_id).unwrap_or_else(|| random_color(rng))
+}
+
+/// Apply changed nets to the chips, and store them in the [`state`] once they are routed
+///
+/// The state is not locked while the chips are written. Since all changes to the nets go through the
+/// net manager, they can't have changed meanwhile.
+///
+/// If the new nets cannot be routed, the state (and the chips) keep the previous nets, and
+/// [`Event::RoutingFailed`] is published.
+async fn apply_nets(
+    nets: Nets,
+    chip_status: &mut ChipStatus,
+    chips: &mut Ch446q<'static, PIO1, 0>,
+    board: &Board,
+) -> RoutingResult {
+    let result = update_chips(&nets, chip_status, chips, board).await;
+    match result {
+        Ok(()) => *state::lock().await.nets_mut() = nets,
+        Err(err) => bus::publish(Event::RoutingFailed(nets.nets[err.net_id().index()].nodes)),
+    }
+    result
+}
+
+async fn update_chips(
+    nets: &Nets,
+    chip_status: &mut ChipStatus,
+    chips: &mut Ch446q<'static, PIO1, 0>,
+    board: &Board,
+) -> Result<(), nets_to_connections::Error> {
     defmt::info!("Nets changed, recomputing connections");
-    chip_status.clear();
-    match nets_to_connections(nets.nets.iter(), chip_status, &board) {
+    // computed separately, so the current status stays intact in case of failure
+    let mut new_status = ChipStatus::default();
+    match nets_to_connections(nets.nets.iter(), &mut new_status, board) {
         Ok(_) => {
             defmt::info!("Connections computed");
-            let mut current_chip = None;
+            *chip_status = new_status;
             chips.reset().await;
-            for crosspoint in chip_status.crosspoints() {
-                if current_chip.is_none() || current_chip.unwrap() != crosspoint.chip {
-                    current_chip = Some(crosspoint.chip);
-                    chips.set_chip(crosspoint.chip);
-                }
-                // defmt::debug!("Set {}/{}/{}", crosspoint.chip.index(), crosspoint.x, crosspoint.y);
-                chips.write(crosspoint.into()).await;
-                Timer::after_micros(100).await;
-            }
+            write_crosspoints(chips, chip_status.crosspoints(), true).await;
+            Ok(())
         },
-        Err(_err) => {
-            defmt::error!("Failed to compute connections");
+        Err(err) => {
+            defmt::error!("Failed to compute connections for net {}", err.net_id().index() + 1);
+            Err(err)
         }
     }
+}
 
-    bus::inject(leds::Message::UpdateFromNets).await;
+/// Close (or open, if `connect` is false) the switches of the given crosspoints
+async fn write_crosspoints(
+    chips: &mut Ch446q<'static, PIO1, 0>,
+    crosspoints: impl Iterator<Item = Crosspoint>,
+    connect: bool,
+) {
+    let mut current_chip = None;
+    for crosspoint in crosspoints {
+        if current_chip.is_none() || current_chip.unwrap() != crosspoint.chip {
+            current_chip = Some(crosspoint.chip);
+            chips.set_chip(crosspoint.chip);
+        }
+        // defmt::debug!("Set {}/{}/{}", crosspoint.chip.index(), crosspoint.x, crosspoint.y);
+        chips.write(Packet::new(crosspoint.x, crosspoint.y, connect)).await;
+        Timer::after_micros(100).await;
+    }
 }
 
 /// Pick a random color, for a net
@@ -120,14 +250,14 @@ fn random_color(rng: &mut SmallRng) -> (u8, u8, u8) {
     let max = r.max(g).max(b);
 
     if max < 0xBB { // if it's a dark color, make it brighter
-        if r > (max - 0x55) { // this value kinda determines the likelihoood of getting secondary colors, so it's tuned to be roughly 50/50 primary and secondaries (rgb are primaries in this case)
-            _ = r.saturating_mul(3);
+        if r > (max.saturating_sub(0x55)) { // this value kinda determines the likelihoood of getting secondary colors, so it's tuned to be roughly 50/50 primary and secondaries (rgb are primaries in this case)
+            r = r.saturating_mul(3);
         }
-        if g > (max - 0x55) {
-            _ = g.saturating_mul(3);
+        if g > (max.saturating_sub(0x55)) {
+            g = g.saturating_mul(3);
         }
-        if b > (max - 0x55) {
-            _ = b.saturating_mul(3);
+        if b > (max.saturating_sub(0x55)) {
+            b = b.saturating_mul(3);
         }
     }
 
@@ -135,13 +265,13 @@ fn random_color(rng: &mut SmallRng) -> (u8, u8, u8) {
 
     if max < 0xAA { // even with multiplying by 3, it's still dark, so we need to make it brighter
         if r == max {
-            _ = r.saturating_mul(4);
+            r = r.saturating_mul(4);
         }
         if g == max {
-            _ = g.saturating_mul(4);
+            g = g.saturating_mul(4);
         }
         if b == max {
-            _ = g.saturating_mul(4);
+            b = b.saturating_mul(4);
         }
     }
 
@@ -0,0 +1,129 @@
+use embassy_futures::select::{select, Either};
+use embassy_rp::flash::{Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex,
+    channel::{Channel, Sender},
+    mutex::Mutex,
+};
+
+use crate::crash_log::{self, CrashLog};
+use crate::settings::{Settings, ENCODED_SIZE};
+use crate::task::watchdog::{Heartbeat, TaskId};
+use crate::{bus, bus::Event, state};
+
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+/// Offset of the flash sector holding the settings (the last one, reserved in `memory.x`)
+const SETTINGS_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+
+/// Offset of the flash sector holding the crash log (the second to last one, reserved in `memory.x`)
+const CRASH_LOG_OFFSET: u32 = SETTINGS_OFFSET - ERASE_SIZE as u32;
+
+pub type SettingsFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+static CHANNEL: bus::Channel<Message> = Channel::new();
+
+static LAST_CRASH: Mutex<ThreadModeRawMutex, Option<CrashLog>> = Mutex::new(None);
+
+/// A [`bus::BusMessage`] targeting the `settings` task.
+pub enum Message {
+    /// Forget the last crash
+    ClearCrashLog,
+}
+
+impl bus::BusMessage for Message {
+    fn sender<'a>() -> Sender<'a, ThreadModeRawMutex, Self, { bus::CHANNEL_SIZE }> {
+        CHANNEL.sender()
+    }
+}
+
+/// Load settings from flash
+///
+/// Falls back to the defaults if no valid settings are stored. Called once during startup,
+/// to initialize the [`state`].
+pub async fn load(flash: &mut SettingsFlash) -> Settings {
+    let mut buf = [0; ENCODED_SIZE];
+    let settings = match flash.blocking_read(SETTINGS_OFFSET, &mut buf) {
+        Ok(()) => Settings::decode(&buf).unwrap_or_default(),
+        Err(e) => {
+            defmt::warn!("Failed to read settings: {}", e);
+            Settings::default()
+        }
+    };
+    settings
+}
+
+/// Returns the most recent crash, if any was recorded
+pub async fn last_crash() -> Option<CrashLog> {
+    LAST_CRASH.lock().await.clone()
+}
+
+/// Load the most recent crash
+///
+/// A crash which caused the last reset is persisted to flash first, so it is still available after a power cycle.
+/// Called once during startup, like [`load`].
+pub async fn load_crash_log(flash: &mut SettingsFlash) {
+    let crash = crash_log::take();
+    crash_log::arm();
+    let crash = match crash {
+        Some(crash) => {
+            defmt::warn!("Reset after crash ({}): {}", crash.kind.label(), crash.message.as_str());
+            write_sector(flash, CRASH_LOG_OFFSET, &crash.encode());
+            Some(crash)
+        }
+        None => {
+            let mut buf = [0; crash_log::ENCODED_SIZE];
+            match flash.blocking_read(CRASH_LOG_OFFSET, &mut buf) {
+                Ok(()) => CrashLog::decode(&buf),
+                Err(e) => {
+                    defmt::warn!("Failed to read crash log: {}", e);
+                    None
+                }
+            }
+        }
+    };
+    *LAST_CRASH.lock().await = crash;
+}
+
+fn store(flash: &mut SettingsFlash, settings: &Settings) {
+    write_sector(flash, SETTINGS_OFFSET, &settings.encode());
+}
+
+/// Erase the sector at `offset`, then write `data` to its beginning
+fn write_sector(flash: &mut SettingsFlash, offset: u32, data: &[u8]) {
+    if let Err(e) = flash.blocking_erase(offset, offset + ERASE_SIZE as u32) {
+        defmt::error!("Failed to erase flash at {:x}: {}", offset, e);
+        return;
+    }
+    if let Err(e) = flash.blocking_write(offset, data) {
+        defmt::error!("Failed to write flash at {:x}: {}", offset, e);
+    }
+}
+
+#[embassy_executor::task]
+pub async fn main(mut flash: SettingsFlash) {
+    let mut events = bus::subscribe();
+    let mut heartbeat = Heartbeat::new(TaskId::Settings);
+    // settings as stored in flash
+    let mut stored = state::lock().await.settings();
+    loop {
+        match select(heartbeat.receive(&CHANNEL), events.next_message_pure()).await {
+            Either::Second(Event::SettingsChanged) => {
+                let settings = state::lock().await.settings();
+                if settings != stored {
+                    store(&mut flash, &settings);
+                    stored = settings;
+                }
+            }
+            Either::Second(_) => {}
+            Either::First(Message::ClearCrashLog) => {
+                if LAST_CRASH.lock().await.take().is_some() {
+                    if let Err(e) = flash.blocking_erase(CRASH_LOG_OFFSET, CRASH_LOG_OFFSET + ERASE_SIZE as u32) {
+                        defmt::error!("Failed to erase crash log: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,18 +1,72 @@
-use embassy_rp::watchdog::Watchdog;
+use core::fmt::Write;
+
+use embassy_futures::select::{select, Either};
+use embassy_rp::{rom_data, watchdog::Watchdog};
 use embassy_sync::{
     blocking_mutex::raw::ThreadModeRawMutex,
     channel::{Channel, Sender},
 };
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 
 use crate::bus;
+use crate::crash_log::{self, CrashKind, CrashLog};
 
 static CHANNEL: bus::Channel<Message> = Channel::new();
 
+/// Time until the hardware watchdog resets the device, unless it is fed
+const WATCHDOG_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Interval in which the hardware watchdog is fed (as long as all tasks are healthy)
+const FEED_INTERVAL: Duration = Duration::from_millis(750);
+
+/// Interval in which supervised tasks check in, see [`Heartbeat`]
+const CHECK_IN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A supervised task which has not checked in for this long is considered stalled
+const STALL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Tasks supervised by the watchdog
+///
+/// Each of these must check in regularly (using a [`Heartbeat`]), otherwise the device resets.
+#[derive(Copy, Clone, defmt::Format)]
+pub enum TaskId {
+    Leds,
+    NetManager,
+    Settings,
+    Dac,
+    Measure,
+}
+
+impl TaskId {
+    const ALL: [TaskId; 5] = [
+        TaskId::Leds,
+        TaskId::NetManager,
+        TaskId::Settings,
+        TaskId::Dac,
+        TaskId::Measure,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TaskId::Leds => "leds",
+            TaskId::NetManager => "net_manager",
+            TaskId::Settings => "settings",
+            TaskId::Dac => "dac",
+            TaskId::Measure => "measure",
+        }
+    }
+}
+
 /// A [`bus::BusMessage`] targeting the `watchdog` task.
 pub enum Message {
     /// Trigger a device reset
     Reset,
+
+    /// Reboot into the USB mass storage bootloader of the RP2040's ROM, to update the firmware
+    RebootToBootloader,
+
+    /// Sent by supervised tasks to signal that they are alive
+    CheckIn(TaskId),
 }
 
 impl bus::BusMessage for Message {
@@ -21,20 +75,98 @@ impl bus::BusMessage for Message {
     }
 }
 
+/// Checks in with the watchdog on behalf of a supervised task
+///
+/// The task must call [`Heartbeat::beat`] regularly (at least every [`CHECK_IN_INTERVAL`]), or wait for
+/// messages using [`Heartbeat::receive`].
+pub struct Heartbeat {
+    task: TaskId,
+    last: Instant,
+}
+
+impl Heartbeat {
+    pub fn new(task: TaskId) -> Self {
+        Self {
+            task,
+            last: Instant::MIN,
+        }
+    }
+
+    /// Check in, if it is due
+    pub async fn beat(&mut self) {
+        if self.last.elapsed() >= CHECK_IN_INTERVAL {
+            bus::inject(Message::CheckIn(self.task)).await;
+            self.last = Instant::now();
+        }
+    }
+
+    /// Wait until the next check in is due
+    pub fn due(&self) -> Timer {
+        Timer::at(self.last + CHECK_IN_INTERVAL)
+    }
+
+    /// Wait for the next message on the given channel, checking in while waiting
+    pub async fn receive<T>(&mut self, channel: &bus::Channel<T>) -> T {
+        loop {
+            self.beat().await;
+            if let Either::First(message) = select(channel.receive(), self.due()).await {
+                return message;
+            }
+        }
+    }
+}
+
 #[embassy_executor::task]
 pub async fn main(mut watchdog: Watchdog) {
-    watchdog.start(Duration::from_millis(1500));
-    // FIXME: when `pause_on_debug` is set, the reset on infinite loops does
-    //   not function at all. If it is not set, probe-rs fails to show defmt
-    //   logs on every second attempt 🤷.
+    watchdog.start(WATCHDOG_TIMEOUT);
+    // When `pause_on_debug` is not set, probe-rs fails to show defmt logs on every second attempt 🤷.
+    // While it is set, the hardware watchdog does not reset the device when a debug probe is attached,
+    // so stalled tasks trigger a reset explicitly (see below). A blocked executor still goes unnoticed then.
     watchdog.pause_on_debug(true);
-    loop {
-        Timer::after_millis(750).await;
-        watchdog.feed();
 
-        if let Ok(message) = CHANNEL.try_receive() {
-            match message {
-                Message::Reset => watchdog.trigger_reset(),
+    let mut last_seen = [Instant::now(); TaskId::ALL.len()];
+    let mut next_feed = Instant::now();
+    // time when the first stalled task was noticed
+    let mut stalled_since = None;
+
+    loop {
+        match select(CHANNEL.receive(), Timer::at(next_feed)).await {
+            Either::First(Message::Reset) => {
+                crash_log::clear();
+                watchdog.trigger_reset();
+            }
+            Either::First(Message::RebootToBootloader) => {
+                crash_log::clear();
+                rom_data::reset_to_usb_boot(0, 0);
+            }
+            Either::First(Message::CheckIn(task)) => last_seen[task as usize] = Instant::now(),
+            Either::Second(()) => {
+                next_feed += FEED_INTERVAL;
+                let mut stalled = CrashLog::new(CrashKind::TaskStalled);
+                for task in TaskId::ALL {
+                    if last_seen[task as usize].elapsed() > STALL_TIMEOUT {
+                        defmt::error!("Task stalled: {}", task);
+                        if !stalled.message.is_empty() {
+                            _ = stalled.write_str(", ");
+                        }
+                        _ = stalled.write_str(task.name());
+                    }
+                }
+                if stalled.message.is_empty() {
+                    watchdog.feed();
+                    if stalled_since.take().is_some() {
+                        // recovered just in time
+                        crash_log::arm();
+                    }
+                } else {
+                    // recorded before the reset, in case the hardware watchdog strikes first
+                    crash_log::record(&stalled);
+                    let since = *stalled_since.get_or_insert(Instant::now());
+                    // the hardware watchdog should have reset the device by now, unless it is paused
+                    if since.elapsed() > WATCHDOG_TIMEOUT {
+                        watchdog.trigger_reset();
+                    }
+                }
             }
         }
     }
@@ -0,0 +1,103 @@
+use embassy_futures::select::{select3, Either3};
+use embassy_rp::peripherals::{PIN_0, PIN_1, UART0, USB};
+use embassy_rp::uart::{self, BufferedUart};
+use embassy_rp::usb::Driver;
+use embassy_time::Timer;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embedded_io_async::{Read, Write};
+
+use crate::shell::Disconnected;
+
+/// Size of each of the UART's ring buffers (RX and TX)
+const BUFFER_SIZE: usize = 256;
+
+/// Baud rate used when the host did not request a usable one
+const DEFAULT_BAUDRATE: u32 = 115200;
+
+/// Bridges a USB CDC interface to the RP2040's UART0
+///
+/// UART0 is connected to the `RP_UART_TX` (GPIO 0) and `RP_UART_RX` (GPIO 1) nodes,
+/// which can be routed onto the breadboard (or the Arduino Nano header) like any other node.
+///
+/// The UART is (re-)configured to match the baud rate requested by the host
+/// via the CDC line coding.
+pub struct UartBridge {
+    uart: UART0,
+    tx_pin: PIN_0,
+    rx_pin: PIN_1,
+    tx_buffer: [u8; BUFFER_SIZE],
+    rx_buffer: [u8; BUFFER_SIZE],
+}
+
+impl UartBridge {
+    pub fn new(uart: UART0, tx_pin: PIN_0, rx_pin: PIN_1) -> Self {
+        Self {
+            uart,
+            tx_pin,
+            rx_pin,
+            tx_buffer: [0; BUFFER_SIZE],
+            rx_buffer: [0; BUFFER_SIZE],
+        }
+    }
+
+    /// Forward data in both directions, until the connection is terminated
+    ///
+    /// The line coding is checked whenever data arrives, and at least every 100ms.
+    /// When the host changes the baud rate, the UART is set up again with the new rate.
+    pub async fn run(&mut self, class: &mut CdcAcmClass<'_, Driver<'_, USB>>) -> Result<(), Disconnected> {
+        let mut usb_buf = [0; 64];
+        let mut uart_buf = [0; 64];
+        // number of bytes in `usb_buf`, that still need to be written to the UART
+        let mut pending = 0;
+        loop {
+            let baudrate = requested_baudrate(class);
+            defmt::info!("UART bridge: {} baud", baudrate);
+
+            let mut config = uart::Config::default();
+            config.baudrate = baudrate;
+            let mut uart = BufferedUart::new(
+                &mut self.uart,
+                crate::Irqs,
+                &mut self.tx_pin,
+                &mut self.rx_pin,
+                &mut self.tx_buffer,
+                &mut self.rx_buffer,
+                config,
+            );
+
+            loop {
+                if pending > 0 {
+                    if let Err(err) = uart.write_all(&usb_buf[..pending]).await {
+                        defmt::warn!("UART bridge: write error: {}", err);
+                    }
+                    pending = 0;
+                }
+
+                let event = select3(
+                    class.read_packet(&mut usb_buf),
+                    uart.read(&mut uart_buf),
+                    Timer::after_millis(100),
+                )
+                .await;
+
+                match event {
+                    Either3::First(n) => pending = n?,
+                    Either3::Second(Ok(n)) => class.write_packet(&uart_buf[..n]).await?,
+                    Either3::Second(Err(err)) => defmt::warn!("UART bridge: read error: {}", err),
+                    Either3::Third(()) => {}
+                }
+
+                if requested_baudrate(class) != baudrate {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn requested_baudrate(class: &CdcAcmClass<'_, Driver<'_, USB>>) -> u32 {
+    match class.line_coding().data_rate() {
+        0 => DEFAULT_BAUDRATE,
+        rate => rate,
+    }
+}
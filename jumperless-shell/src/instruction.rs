@@ -0,0 +1,457 @@
+use jumperless_common::{
+    board::Node,
+    color::{parse_color, Color, Palette},
+    dac::{parse_frequency, DacChannel, DacOutput, Wave, Waveform},
+    parse_voltage, Millivolts, SupplySwitchPos,
+};
+
+/// An instruction entered into the shell
+#[derive(Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Help,
+    Info,
+    Reset,
+    Bootloader,
+    /// Switch the shell to (`true`) or from (`false`) the machine protocol, see [`crate::Shell`]
+    Machine(bool),
+    RainbowBounce,
+    SetSwitchPos(SupplySwitchPos),
+    PrintSwitchPos,
+    Clear,
+    AddBridge(Node, Node),
+    TestLed(usize),
+    DumpLeds,
+    SetBrightness(u8),
+    PrintBrightness,
+    /// Set the current (in mA) all LEDs together may draw
+    SetPowerBudget(u16),
+    PrintPowerBudget,
+    Highlight(Node),
+    HighlightNet(u8),
+    ClearHighlights,
+    SetColor(u8, Color),
+    SetPalette(Palette),
+    PrintPalette,
+    PrintLastCrash,
+    ClearLastCrash,
+    SetDac(DacChannel, DacOutput),
+    MeasureVoltage(Node),
+    MeasureCurrent,
+    /// Measure the current repeatedly (every given number of milliseconds), until interrupted
+    LogCurrent(u32),
+}
+
+/// Default interval of `measure current log` (in milliseconds)
+pub const DEFAULT_LOG_INTERVAL: u32 = 100;
+
+/// Shortest interval of `measure current log` (in milliseconds)
+pub const MIN_LOG_INTERVAL: u32 = 10;
+
+/// Highest power budget (in mA) accepted by `power-budget`
+pub const MAX_POWER_BUDGET: u16 = 5000;
+
+/// Names of all instructions, used for tab completion
+pub const INSTRUCTIONS: &[&str] = &[
+    "help",
+    "info",
+    "reset",
+    "bootloader",
+    "machine",
+    "rainbow-bounce",
+    "switch-pos",
+    "clear",
+    "add-bridge",
+    "test-led",
+    "dump-leds",
+    "brightness",
+    "power-budget",
+    "highlight",
+    "highlight-net",
+    "set-color",
+    "palette",
+    "last-crash",
+    "dac",
+    "measure",
+];
+
+pub(crate) const HELP: &[&[u8]] = &[
+    b"Available instructions:\r\n",
+    b"  help                      Print this help text\r\n",
+    b"  info                      Print board revision, firmware version and serial number\r\n",
+    b"  reset                     Reset (reboot) the device\r\n",
+    b"  bootloader                Reboot into the USB bootloader, to update the firmware\r\n",
+    b"  machine [on|off]          Switch to (or from) the machine protocol: no echo or prompt,\r\n",
+    b"                            every reply ends with a line reading OK or ERR\r\n",
+    b"  rainbow-bounce            Play rainbow animation\r\n",
+    b"  switch-pos [<5V|3V3|8V>]  Get/set switch position\r\n",
+    b"  clear                     Clear all connections\r\n",
+    b"  add-bridge <node> <node>  Connect two nodes\r\n",
+    b"  test-led <led-number>     Test an LED\r\n",
+    b"  dump-leds                 Print colors of all LEDs (as rrggbb, for render-leds)\r\n",
+    b"  brightness [<0-100>]      Get/set LED brightness (in percent)\r\n",
+    b"  power-budget [<mA>]       Get/set current limit of all LEDs together (in mA)\r\n",
+    b"  highlight <node>          Blink the LEDs of the node's net, then keep them brighter\r\n",
+    b"  highlight clear           Stop highlighting all nets\r\n",
+    b"  highlight-net <net-id>    Blink the LEDs of a net, then keep them brighter\r\n",
+    b"  set-color <net-id> <color>\r\n",
+    b"                            Set color of a net (#rrggbb or a name like red)\r\n",
+    b"  palette [<random|contrast|rainbow>]\r\n",
+    b"                            Get/set palette to pick net colors from\r\n",
+    b"  last-crash [clear]        Show (or forget) why the device crashed last\r\n",
+    b"  dac <0|1> <volts>         Set the voltage of a DAC output\r\n",
+    b"  dac <0|1> <sine|square|triangle> <hz> <low-volts> <high-volts>\r\n",
+    b"                            Play a waveform on a DAC output\r\n",
+    b"  measure <node>            Measure the voltage of a node (and its net)\r\n",
+    b"  measure current           Measure the current from ISENSE_PLUS to ISENSE_MINUS\r\n",
+    b"  measure current log [<interval-ms>]\r\n",
+    b"                            Print current measurements (as CSV) until a key is pressed\r\n",
+];
+
+/// Kind of value expected by an instruction argument, used for tab completion
+pub enum Argument {
+    Node,
+    Palette,
+    Waveform,
+    Other,
+}
+
+impl Instruction {
+    /// Kind of the argument at `index` (starting at 0) of the given instruction
+    pub fn argument(instruction: &str, index: usize) -> Argument {
+        match (instruction, index) {
+            ("add-bridge", 0 | 1) | ("highlight", 0) | ("measure", 0) => Argument::Node,
+            ("palette", 0) => Argument::Palette,
+            ("dac", 1) => Argument::Waveform,
+            _ => Argument::Other,
+        }
+    }
+
+    /// Parse a line of input
+    ///
+    /// Returns `None` if the line is blank, or an error message to print if it is invalid.
+    pub fn parse(input: &str) -> Result<Option<Instruction>, &'static [u8]> {
+        let mut tokens = input.trim().split_ascii_whitespace();
+        if let Some(token) = tokens.next() {
+            match token {
+                "help" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::Help))
+                }
+                "info" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::Info))
+                }
+                "reset" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::Reset))
+                }
+                "bootloader" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::Bootloader))
+                }
+                "machine" => match tokens.next() {
+                    None | Some("on") => {
+                        no_more_args(&mut tokens)?;
+                        Ok(Some(Instruction::Machine(true)))
+                    }
+                    Some("off") => {
+                        no_more_args(&mut tokens)?;
+                        Ok(Some(Instruction::Machine(false)))
+                    }
+                    Some(_) => Err(b"Error: invalid argument\r\n"),
+                },
+                "rainbow-bounce" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::RainbowBounce))
+                }
+                "switch-pos" => {
+                    if let Some(pos) = tokens.next() {
+                        no_more_args(&mut tokens)?;
+                        if let Some(pos) = SupplySwitchPos::parse(pos) {
+                            Ok(Some(Instruction::SetSwitchPos(pos)))
+                        } else {
+                            Err(b"Error: invalid argument\r\n")
+                        }
+                    } else {
+                        Ok(Some(Instruction::PrintSwitchPos))
+                    }
+                }
+                "clear" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::Clear))
+                }
+                "add-bridge" => {
+                    let a = shift_arg(&mut tokens)?;
+                    let b = shift_arg(&mut tokens)?;
+                    no_more_args(&mut tokens)?;
+                    if let Ok(a) = a.parse::<Node>() {
+                        if let Ok(b) = b.parse::<Node>() {
+                            Ok(Some(Instruction::AddBridge(a, b)))
+                        } else {
+                            Err(b"Error: invalid second node\r\n")
+                        }
+                    } else {
+                        Err(b"Error: invalid first node\r\n")
+                    }
+                }
+                "test-led" => {
+                    let i = shift_arg(&mut tokens)?;
+                    no_more_args(&mut tokens)?;
+                    if let Ok(i) = i.parse::<usize>() {
+                        Ok(Some(Instruction::TestLed(i)))
+                    } else {
+                        Err(b"Error: invalid led number\r\n")
+                    }
+                }
+                "last-crash" => match tokens.next() {
+                    None => Ok(Some(Instruction::PrintLastCrash)),
+                    Some("clear") => {
+                        no_more_args(&mut tokens)?;
+                        Ok(Some(Instruction::ClearLastCrash))
+                    }
+                    Some(_) => Err(b"Error: invalid argument\r\n"),
+                },
+                "dump-leds" => {
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::DumpLeds))
+                }
+                "brightness" => {
+                    if let Some(brightness) = tokens.next() {
+                        no_more_args(&mut tokens)?;
+                        match brightness.parse::<u8>() {
+                            Ok(brightness) if brightness <= 100 => Ok(Some(Instruction::SetBrightness(brightness))),
+                            _ => Err(b"Error: brightness must be between 0 and 100\r\n"),
+                        }
+                    } else {
+                        Ok(Some(Instruction::PrintBrightness))
+                    }
+                }
+                "power-budget" => {
+                    if let Some(budget) = tokens.next() {
+                        no_more_args(&mut tokens)?;
+                        match budget.strip_suffix("mA").unwrap_or(budget).parse::<u16>() {
+                            Ok(budget) if budget <= MAX_POWER_BUDGET => Ok(Some(Instruction::SetPowerBudget(budget))),
+                            _ => Err(b"Error: power budget must be between 0 and 5000mA\r\n"),
+                        }
+                    } else {
+                        Ok(Some(Instruction::PrintPowerBudget))
+                    }
+                }
+                "highlight" => {
+                    let node = shift_arg(&mut tokens)?;
+                    no_more_args(&mut tokens)?;
+                    if node == "clear" {
+                        Ok(Some(Instruction::ClearHighlights))
+                    } else if let Ok(node) = node.parse::<Node>() {
+                        Ok(Some(Instruction::Highlight(node)))
+                    } else {
+                        Err(b"Error: invalid node\r\n")
+                    }
+                }
+                "highlight-net" => {
+                    let id = shift_arg(&mut tokens)?;
+                    no_more_args(&mut tokens)?;
+                    match id.parse::<u8>() {
+                        Ok(id) if id > 0 => Ok(Some(Instruction::HighlightNet(id))),
+                        _ => Err(b"Error: invalid net id\r\n"),
+                    }
+                }
+                "set-color" => {
+                    let id = shift_arg(&mut tokens)?;
+                    let color = shift_arg(&mut tokens)?;
+                    no_more_args(&mut tokens)?;
+                    let Some(id) = id.parse::<u8>().ok().filter(|id| *id > 0) else {
+                        return Err(b"Error: invalid net id\r\n");
+                    };
+                    if let Some(color) = parse_color(color) {
+                        Ok(Some(Instruction::SetColor(id, color)))
+                    } else {
+                        Err(b"Error: invalid color\r\n")
+                    }
+                }
+                "palette" => {
+                    if let Some(palette) = tokens.next() {
+                        no_more_args(&mut tokens)?;
+                        if let Some(palette) = Palette::parse(palette) {
+                            Ok(Some(Instruction::SetPalette(palette)))
+                        } else {
+                            Err(b"Error: no such palette\r\n")
+                        }
+                    } else {
+                        Ok(Some(Instruction::PrintPalette))
+                    }
+                }
+                "dac" => {
+                    let Some(channel) = DacChannel::parse(shift_arg(&mut tokens)?) else {
+                        return Err(b"Error: invalid DAC channel\r\n");
+                    };
+                    let arg = shift_arg(&mut tokens)?;
+                    let output = if let Some(waveform) = Waveform::parse(arg) {
+                        let Some(frequency) = parse_frequency(shift_arg(&mut tokens)?) else {
+                            return Err(b"Error: invalid frequency\r\n");
+                        };
+                        let low = dac_voltage(channel, shift_arg(&mut tokens)?)?;
+                        let high = dac_voltage(channel, shift_arg(&mut tokens)?)?;
+                        DacOutput::Wave(Wave { waveform, frequency, low, high })
+                    } else {
+                        DacOutput::Voltage(dac_voltage(channel, arg)?)
+                    };
+                    no_more_args(&mut tokens)?;
+                    Ok(Some(Instruction::SetDac(channel, output)))
+                }
+                "measure" => match shift_arg(&mut tokens)? {
+                    "current" => match tokens.next() {
+                        None => Ok(Some(Instruction::MeasureCurrent)),
+                        Some("log") => {
+                            let interval = match tokens.next() {
+                                Some(interval) => match interval.parse::<u32>() {
+                                    Ok(interval) if interval >= MIN_LOG_INTERVAL => interval,
+                                    _ => return Err(b"Error: interval must be at least 10ms\r\n"),
+                                },
+                                None => DEFAULT_LOG_INTERVAL,
+                            };
+                            no_more_args(&mut tokens)?;
+                            Ok(Some(Instruction::LogCurrent(interval)))
+                        }
+                        Some(_) => Err(b"Error: invalid argument\r\n"),
+                    },
+                    node => {
+                        no_more_args(&mut tokens)?;
+                        if let Ok(node) = node.parse::<Node>() {
+                            Ok(Some(Instruction::MeasureVoltage(node)))
+                        } else {
+                            Err(b"Error: invalid node\r\n")
+                        }
+                    }
+                },
+                // "chipdump" => {
+                //     no_more_args(&mut tokens)?;
+                // }
+                _ => Err(b"Error: no such instruction\r\n"),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn shift_arg<'a, T: Iterator<Item = &'a str>>(tokens: &mut T) -> Result<&'a str, &'static [u8]> {
+    match tokens.next() {
+        Some(arg) => Ok(arg),
+        None => Err(b"Error: missing argument\r\n"),
+    }
+}
+
+/// Parse a voltage which the given DAC channel can produce
+fn dac_voltage(channel: DacChannel, arg: &str) -> Result<Millivolts, &'static [u8]> {
+    match parse_voltage(arg) {
+        Some(voltage) if channel.calibration().contains(voltage) => Ok(voltage),
+        Some(_) => Err(b"Error: voltage out of range\r\n"),
+        None => Err(b"Error: invalid voltage\r\n"),
+    }
+}
+
+fn no_more_args<'a, T: Iterator<Item = &'a str>>(tokens: &mut T) -> Result<(), &'static [u8]> {
+    match tokens.next() {
+        Some(_) => Err(b"Error: unexpected extra arguments\r\n"),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Instruction::parse("  "), Ok(None));
+        assert_eq!(Instruction::parse("help"), Ok(Some(Instruction::Help)));
+        assert_eq!(Instruction::parse("bootloader"), Ok(Some(Instruction::Bootloader)));
+        assert_eq!(Instruction::parse("info"), Ok(Some(Instruction::Info)));
+        assert_eq!(Instruction::parse("machine"), Ok(Some(Instruction::Machine(true))));
+        assert_eq!(Instruction::parse("machine off"), Ok(Some(Instruction::Machine(false))));
+        assert_eq!(
+            Instruction::parse(" add-bridge  NANO_A7 42 "),
+            Ok(Some(Instruction::AddBridge(Node::NANO_A7, Node::_42)))
+        );
+        assert_eq!(
+            Instruction::parse("switch-pos 3V3"),
+            Ok(Some(Instruction::SetSwitchPos(SupplySwitchPos::_3V3)))
+        );
+        assert_eq!(Instruction::parse("switch-pos"), Ok(Some(Instruction::PrintSwitchPos)));
+        assert_eq!(Instruction::parse("test-led 7"), Ok(Some(Instruction::TestLed(7))));
+        assert_eq!(Instruction::parse("dump-leds"), Ok(Some(Instruction::DumpLeds)));
+        assert_eq!(Instruction::parse("last-crash"), Ok(Some(Instruction::PrintLastCrash)));
+        assert_eq!(Instruction::parse("last-crash clear"), Ok(Some(Instruction::ClearLastCrash)));
+        assert_eq!(Instruction::parse("brightness 40"), Ok(Some(Instruction::SetBrightness(40))));
+        assert_eq!(Instruction::parse("brightness"), Ok(Some(Instruction::PrintBrightness)));
+        assert_eq!(Instruction::parse("power-budget 500"), Ok(Some(Instruction::SetPowerBudget(500))));
+        assert_eq!(Instruction::parse("power-budget 0mA"), Ok(Some(Instruction::SetPowerBudget(0))));
+        assert_eq!(Instruction::parse("power-budget"), Ok(Some(Instruction::PrintPowerBudget)));
+        assert_eq!(Instruction::parse("highlight NANO_D5"), Ok(Some(Instruction::Highlight(Node::NANO_D5))));
+        assert_eq!(Instruction::parse("highlight clear"), Ok(Some(Instruction::ClearHighlights)));
+        assert_eq!(Instruction::parse("highlight-net 9"), Ok(Some(Instruction::HighlightNet(9))));
+        assert_eq!(
+            Instruction::parse("set-color 8 #00ff80"),
+            Ok(Some(Instruction::SetColor(8, (0x00, 0xFF, 0x80))))
+        );
+        assert_eq!(
+            Instruction::parse("palette contrast"),
+            Ok(Some(Instruction::SetPalette(Palette::Contrast)))
+        );
+        assert_eq!(Instruction::parse("palette"), Ok(Some(Instruction::PrintPalette)));
+        assert_eq!(
+            Instruction::parse("dac 1 -2.5"),
+            Ok(Some(Instruction::SetDac(DacChannel::Dac1, DacOutput::Voltage(-2500))))
+        );
+        assert_eq!(
+            Instruction::parse("dac 0 sine 0.5 1 3.3"),
+            Ok(Some(Instruction::SetDac(
+                DacChannel::Dac0,
+                DacOutput::Wave(Wave { waveform: Waveform::Sine, frequency: 500, low: 1000, high: 3300 })
+            )))
+        );
+        assert_eq!(Instruction::parse("measure current"), Ok(Some(Instruction::MeasureCurrent)));
+        assert_eq!(Instruction::parse("measure 17"), Ok(Some(Instruction::MeasureVoltage(Node::_17))));
+        assert_eq!(
+            Instruction::parse("measure current log"),
+            Ok(Some(Instruction::LogCurrent(DEFAULT_LOG_INTERVAL)))
+        );
+        assert_eq!(Instruction::parse("measure current log 20"), Ok(Some(Instruction::LogCurrent(20))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Instruction::parse("frobnicate"), Err(&b"Error: no such instruction\r\n"[..]));
+        assert_eq!(Instruction::parse("help me"), Err(&b"Error: unexpected extra arguments\r\n"[..]));
+        assert_eq!(Instruction::parse("add-bridge 1"), Err(&b"Error: missing argument\r\n"[..]));
+        assert_eq!(Instruction::parse("add-bridge X 1"), Err(&b"Error: invalid first node\r\n"[..]));
+        assert_eq!(Instruction::parse("add-bridge 1 X"), Err(&b"Error: invalid second node\r\n"[..]));
+        assert_eq!(Instruction::parse("switch-pos 12V"), Err(&b"Error: invalid argument\r\n"[..]));
+        assert_eq!(Instruction::parse("machine maybe"), Err(&b"Error: invalid argument\r\n"[..]));
+        assert_eq!(Instruction::parse("test-led one"), Err(&b"Error: invalid led number\r\n"[..]));
+        assert_eq!(
+            Instruction::parse("brightness 101"),
+            Err(&b"Error: brightness must be between 0 and 100\r\n"[..])
+        );
+        assert_eq!(
+            Instruction::parse("power-budget 5001"),
+            Err(&b"Error: power budget must be between 0 and 5000mA\r\n"[..])
+        );
+        assert_eq!(Instruction::parse("highlight X"), Err(&b"Error: invalid node\r\n"[..]));
+        assert_eq!(Instruction::parse("highlight-net 0"), Err(&b"Error: invalid net id\r\n"[..]));
+        assert_eq!(Instruction::parse("set-color 8 #00ff"), Err(&b"Error: invalid color\r\n"[..]));
+        assert_eq!(Instruction::parse("palette pastel"), Err(&b"Error: no such palette\r\n"[..]));
+        assert_eq!(Instruction::parse("dac 2 1"), Err(&b"Error: invalid DAC channel\r\n"[..]));
+        assert_eq!(Instruction::parse("dac 0 1V5"), Err(&b"Error: invalid voltage\r\n"[..]));
+        assert_eq!(Instruction::parse("dac 1 12"), Err(&b"Error: voltage out of range\r\n"[..]));
+        assert_eq!(Instruction::parse("dac 0 square 1000 0 1"), Err(&b"Error: invalid frequency\r\n"[..]));
+        assert_eq!(Instruction::parse("dac 0 square 10 0"), Err(&b"Error: missing argument\r\n"[..]));
+        assert_eq!(
+            Instruction::parse("measure current log 5"),
+            Err(&b"Error: interval must be at least 10ms\r\n"[..])
+        );
+        assert_eq!(Instruction::parse("measure power"), Err(&b"Error: invalid node\r\n"[..]));
+        assert_eq!(Instruction::parse("measure 17 18"), Err(&b"Error: unexpected extra arguments\r\n"[..]));
+    }
+}
@@ -0,0 +1,564 @@
+#![cfg_attr(not(test), no_std)]
+
+//! Transport-agnostic core of the jumperless shell
+//!
+//! The [`Shell`] reads input from anything implementing [`Read`], takes care of line editing,
+//! history and tab completion, and writes its output to the same transport (via [`Write`]).
+//!
+//! Parsed [`Instruction`]s are handed to an [`Executor`], which carries them out.
+//!
+//! # Machine protocol
+//!
+//! Programs talking to the shell can switch it to the machine protocol with `machine` (and back with
+//! `machine off`). Then input is not echoed, no prompt is printed, and line editing is disabled. Each line
+//! (terminated by `\r` or `\n`) holds one instruction. Its reply consists of the instruction's output, followed
+//! by a line reading `OK`, or `ERR` if the instruction failed (after printing an `Error: ...` line).
+//!
+//! Instructions which keep running until interrupted (like `measure current log`) stop on the next input,
+//! which is discarded. Instructions resetting the device (like `bootloader`) don't reply; the connection drops instead.
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{ErrorType, Read, Write};
+use heapless::{String, Vec};
+use jumperless_common::{board::Node, color::Palette, dac::Waveform};
+use line_buffer::{complete, Completion, History, LineBuffer};
+
+mod instruction;
+pub use instruction::{Argument, Instruction, INSTRUCTIONS};
+
+/// Carries out instructions on behalf of the [`Shell`]
+#[allow(async_fn_in_trait)]
+pub trait Executor {
+    /// Execute given instruction
+    ///
+    /// Output for the user can be written to `output`. Instructions which keep running until interrupted
+    /// (like `measure current log`) can read from it too, to notice the user pressing a key.
+    async fn execute<W: Read + Write>(&mut self, instruction: Instruction, output: &mut W) -> Result<(), W::Error>;
+}
+
+pub struct Overflow;
+
+impl From<Overflow> for &'static [u8] {
+    fn from(_: Overflow) -> Self {
+        "Input buffer overflow".as_bytes()
+    }
+}
+
+/// Progress of parsing an escape sequence
+enum Escape {
+    /// Not within an escape sequence
+    None,
+    /// Saw `ESC`
+    Esc,
+    /// Saw `ESC [`, followed by the collected parameter bytes
+    Csi(Vec<u8, 8>),
+    /// Saw `ESC O`
+    Ss3,
+}
+
+/// Wraps the output of an instruction, noticing whether it printed an error
+///
+/// By convention, errors are reported on a line of their own starting with `Error: `.
+struct ErrorDetector<'a, T> {
+    io: &'a mut T,
+    // nothing was written yet, or the last write ended a line
+    line_start: bool,
+    failed: bool,
+}
+
+impl<'a, T> ErrorDetector<'a, T> {
+    fn new(io: &'a mut T) -> Self {
+        Self { io, line_start: true, failed: false }
+    }
+}
+
+impl<T: ErrorType> ErrorType for ErrorDetector<'_, T> {
+    type Error = T::Error;
+}
+
+impl<T: Read> Read for ErrorDetector<'_, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, T::Error> {
+        self.io.read(buf).await
+    }
+}
+
+impl<T: Write> Write for ErrorDetector<'_, T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, T::Error> {
+        let n = self.io.write(buf).await?;
+        let written = &buf[..n];
+        if self.line_start && written.starts_with(b"Error: ") {
+            self.failed = true;
+        }
+        if !written.is_empty() {
+            self.line_start = written.ends_with(b"\n");
+        }
+        Ok(n)
+    }
+
+    async fn flush(&mut self) -> Result<(), T::Error> {
+        self.io.flush().await
+    }
+}
+
+/// Number of lines kept in the shell's history
+const HISTORY_SIZE: usize = 16;
+
+pub struct Shell<T, E, const BUF_SIZE: usize> {
+    io: T,
+    executor: E,
+    buffer: LineBuffer<BUF_SIZE>,
+    history: History<BUF_SIZE, HISTORY_SIZE>,
+    // speaking the machine protocol (see module docs)?
+    machine: bool,
+}
+
+impl<T: Read + Write, E: Executor, const BUF_SIZE: usize> Shell<T, E, BUF_SIZE> {
+    pub fn new(io: T, executor: E) -> Self {
+        Self {
+            io,
+            executor,
+            buffer: LineBuffer::new(),
+            history: History::new(),
+            machine: false,
+        }
+    }
+
+    /// Run the shell, until reading or writing fails (usually because the connection was terminated)
+    ///
+    /// Reads input, filling the input buffer, then parses
+    /// and executes instructions when ENTER ('\r') is pressed.
+    pub async fn run(&mut self) -> Result<(), T::Error> {
+        let mut buf = [0; 64];
+        // progress within an escape sequence
+        let mut escape = Escape::None;
+        // was the previous character a TAB?
+        let mut tab = false;
+        // in machine mode: the current line overflowed the input buffer, and is discarded
+        let mut overflowed = false;
+        loop {
+            let n = self.io.read(&mut buf).await?;
+
+            let mut submit = false;
+
+            for &c in &buf[..n] {
+                if self.machine {
+                    self.machine_input(c, &mut overflowed).await?;
+                    continue;
+                }
+                let repeated_tab = tab;
+                tab = c == b'\t';
+                match core::mem::replace(&mut escape, Escape::None) {
+                    Escape::Csi(mut params) => {
+                        if (0x30..=0x3F).contains(&c) {
+                            // parameter byte; sequences with too many of them are dropped
+                            if params.push(c).is_ok() {
+                                escape = Escape::Csi(params);
+                            }
+                        } else {
+                            self.control_sequence(&params, c);
+                        }
+                    }
+                    Escape::Ss3 => match c {
+                        b'F' => self.buffer.move_end(),
+                        b'H' => self.buffer.move_home(),
+                        _ => log::debug!("Unhandled SS3: {}", c),
+                    },
+                    Escape::Esc => match c {
+                        b'[' => escape = Escape::Csi(Vec::new()),
+                        b'O' => escape = Escape::Ss3,
+                        b'b' => self.buffer.move_word_left(), // Alt+B
+                        b'f' => self.buffer.move_word_right(), // Alt+F
+                        8 | 127 => self.buffer.kill_word_left(), // Alt+BACKSPACE
+                        _ => log::debug!("Unhandled escape: {}", c),
+                    },
+                    Escape::None => {
+                        if c == b'\r' { // ENTER
+                            submit = true;
+                        } else if c == 27 { // ESC
+                            escape = Escape::Esc;
+                        } else if c == b'\t' { // TAB
+                            self.complete(repeated_tab).await?;
+                        } else if c == 1 { // Ctrl+A
+                            self.buffer.move_home();
+                        } else if c == 3 { // Ctrl+C
+                            self.buffer.reset();
+                            self.io.write_all(b"\r\n^C\r\n").await?;
+                        } else if c == 5 { // Ctrl+E
+                            self.buffer.move_end();
+                        } else if c == 11 { // Ctrl+K
+                            self.buffer.kill_to_end();
+                        } else if c == 14 { // Ctrl+N
+                            self.history.next(&mut self.buffer);
+                        } else if c == 16 { // Ctrl+P
+                            self.history.previous(&mut self.buffer);
+                        } else if c == 21 { // Ctrl+U
+                            self.buffer.kill_to_start();
+                        } else if c == 23 { // Ctrl+W
+                            self.buffer.kill_word_left();
+                        } else if c == 25 { // Ctrl+Y
+                            self.buffer.yank();
+                        } else if c == 127 { // BACKSPACE
+                            self.buffer.backspace();
+                        } else if c.is_ascii_graphic() || c == b' ' {
+                            if self.buffer.insert(c).is_err() {
+                                self.buffer.reset();
+                                self.io.write_all(b"\r\n -- overflow; buffer cleared --\r\n").await?;
+                            }
+                        } else if c.is_ascii_control() {
+                            log::debug!("Unhandled control character: {}", c);
+                        };
+                    }
+                }
+            }
+
+            if self.machine {
+                continue;
+            }
+
+            self.prompt().await?;
+
+            if submit {
+                self.io.write_all(b"\r\n").await?;
+                self.process().await?;
+            }
+        }
+    }
+
+    /// Handle a character of input in machine mode
+    ///
+    /// Lines are processed as soon as they are complete, without echoing anything.
+    async fn machine_input(&mut self, c: u8, overflowed: &mut bool) -> Result<(), T::Error> {
+        if c == b'\r' || c == b'\n' {
+            if core::mem::take(overflowed) {
+                self.buffer.reset();
+            } else {
+                self.process().await?;
+            }
+        } else if (c.is_ascii_graphic() || c == b' ') && !*overflowed && self.buffer.insert(c).is_err() {
+            *overflowed = true;
+            self.io.write_all(b"Error: input buffer overflow\r\nERR\r\n").await?;
+        }
+        Ok(())
+    }
+
+    /// Handle a complete control sequence (`ESC [ <params> <c>`)
+    fn control_sequence(&mut self, params: &[u8], c: u8) {
+        match (params, c) {
+            (b"", b'A') => self.history.previous(&mut self.buffer), // UP
+            (b"", b'B') => self.history.next(&mut self.buffer), // DOWN
+            (b"", b'C') => self.buffer.move_right(), // RIGHT
+            (b"", b'D') => self.buffer.move_left(), // LEFT
+            (b"1;3" | b"1;5", b'C') => self.buffer.move_word_right(), // Alt/Ctrl+RIGHT
+            (b"1;3" | b"1;5", b'D') => self.buffer.move_word_left(), // Alt/Ctrl+LEFT
+            (b"", b'F') | (b"4" | b"8", b'~') => self.buffer.move_end(), // END
+            (b"", b'H') | (b"1" | b"7", b'~') => self.buffer.move_home(), // HOME
+            (b"3", b'~') => self.buffer.delete(), // DELETE
+            _ => log::debug!("Unhandled CSI: {:?} {}", params, c),
+        }
+    }
+
+    /// (Re-) print the prompt, including the input buffer
+    async fn prompt(&mut self) -> Result<(), T::Error> {
+        // print prompt at beginning of line, followed by the current input buffer
+        self.io.write_all(b"\r> ").await?;
+        self.io.write_all(self.buffer.content()).await?;
+
+        // clear the rest of the line, to deal with backspace and shorter history entries
+        self.io.write_all(b"\x1b[K").await?;
+
+        // move cursor to correct position
+        let mut cursor: String<16> = String::new();
+        _ = write!(cursor, "\r\x1b[{}C", self.buffer.cursor() + 2);
+        self.io.write_all(cursor.as_bytes()).await?;
+        self.io.flush().await
+    }
+
+    /// Complete the instruction or argument left of the cursor
+    ///
+    /// Completes as far as the candidates agree. If `list` is set (TAB was pressed twice),
+    /// all remaining candidates are printed as well.
+    async fn complete(&mut self, list: bool) -> Result<(), T::Error> {
+        let content = &self.buffer.content()[..self.buffer.cursor()];
+        let Ok(content) = core::str::from_utf8(content) else {
+            return Ok(());
+        };
+        let word_start = content.len() - self.buffer.word_before_cursor().len();
+        let mut preceding = content[..word_start].split_ascii_whitespace();
+        // `None` if the word is the instruction itself
+        let argument = preceding
+            .next()
+            .map(|instruction| Instruction::argument(instruction, preceding.count()));
+        match argument {
+            None => self.complete_from(INSTRUCTIONS.iter().copied(), list).await,
+            Some(Argument::Node) => self.complete_from(Node::ALL.iter().map(Node::as_str), list).await,
+            Some(Argument::Palette) => self.complete_from(Palette::ALL.iter().map(Palette::label), list).await,
+            Some(Argument::Waveform) => self.complete_from(Waveform::ALL.iter().map(Waveform::label), list).await,
+            Some(Argument::Other) => Ok(()),
+        }
+    }
+
+    async fn complete_from(
+        &mut self,
+        candidates: impl Iterator<Item = &'static str> + Clone,
+        list: bool,
+    ) -> Result<(), T::Error> {
+        let Ok(prefix) = core::str::from_utf8(self.buffer.word_before_cursor()) else {
+            return Ok(());
+        };
+        let prefix_len = prefix.len();
+        let (completed, unique) = match complete(prefix, candidates.clone()) {
+            Completion::None => return Ok(()),
+            Completion::Unique(candidate) => (candidate, true),
+            Completion::Ambiguous(common) => (common, false),
+        };
+        for &c in &completed.as_bytes()[prefix_len..] {
+            _ = self.buffer.insert(c);
+        }
+        if unique {
+            _ = self.buffer.insert(b' ');
+        } else if list {
+            self.io.write_all(b"\r\n").await?;
+            for candidate in candidates.filter(|candidate| candidate.starts_with(completed)) {
+                self.io.write_all(candidate.as_bytes()).await?;
+                self.io.write_all(b"  ").await?;
+            }
+            self.io.write_all(b"\r\n").await?;
+        }
+        Ok(())
+    }
+
+    /// Process the input buffer, and execute any instruction found
+    ///
+    /// In machine mode (or when switching to or from it), the reply is terminated by `OK` or `ERR`.
+    async fn process(&mut self) -> Result<(), T::Error> {
+        let machine = self.machine;
+        let buffer = self.buffer.content();
+        if buffer.is_empty() {
+            if !machine {
+                self.prompt().await?;
+            }
+            return Ok(());
+        }
+        if !machine {
+            self.history.push(buffer);
+        }
+        let mut output = ErrorDetector::new(&mut self.io);
+        if let Ok(input) = core::str::from_utf8(buffer) {
+            match Instruction::parse(input) {
+                Ok(Some(Instruction::Help)) => {
+                    for line in instruction::HELP {
+                        output.write_all(line).await?;
+                    }
+                }
+                Ok(Some(Instruction::Machine(on))) => self.machine = on,
+                Ok(Some(instruction)) => self.executor.execute(instruction, &mut output).await?,
+                Ok(None) => {}
+                Err(message) => output.write_all(message).await?,
+            }
+        }
+        let failed = output.failed;
+        self.buffer.reset();
+        if machine || self.machine {
+            self.io.write_all(if failed { b"ERR\r\n" } else { b"OK\r\n" }).await?;
+            self.io.flush().await?;
+        }
+        if !self.machine {
+            self.prompt().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embassy_futures::block_on;
+    use embedded_io_async::ErrorKind;
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    #[derive(Debug)]
+    struct Disconnected;
+
+    impl embedded_io_async::Error for Disconnected {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::NotConnected
+        }
+    }
+
+    /// Hands out one chunk of input per read, and collects all output
+    ///
+    /// Disconnects once all input has been read.
+    struct MockIo {
+        input: VecDeque<&'static [u8]>,
+        output: Vec<u8>,
+    }
+
+    impl ErrorType for MockIo {
+        type Error = Disconnected;
+    }
+
+    impl Read for MockIo {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Disconnected> {
+            let chunk = self.input.pop_front().ok_or(Disconnected)?;
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl Write for MockIo {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Disconnected> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Records all instructions it is asked to execute
+    #[derive(Default)]
+    struct MockExecutor {
+        executed: Vec<Instruction>,
+    }
+
+    impl Executor for MockExecutor {
+        async fn execute<W: Read + Write>(&mut self, instruction: Instruction, output: &mut W) -> Result<(), W::Error> {
+            let result = match instruction {
+                Instruction::PrintBrightness => output.write_all(b"40%\r\n").await,
+                Instruction::HighlightNet(_) => output.write_all(b"Error: no such net\r\n").await,
+                Instruction::LogCurrent(_) => {
+                    // like the real thing, stream until any input arrives
+                    output.write_all(b"time_ms,current_ua,bus_voltage_mv\r\n").await?;
+                    output.read(&mut [0; 64]).await.map(|_| ())
+                }
+                _ => Ok(()),
+            };
+            self.executed.push(instruction);
+            result
+        }
+    }
+
+    /// Run a shell on the given input chunks, until it runs out of input
+    ///
+    /// Returns everything the shell printed, and the instructions it executed.
+    fn run(input: &[&'static [u8]]) -> (std::string::String, Vec<Instruction>) {
+        let io = MockIo {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        };
+        let mut shell: Shell<_, _, 62> = Shell::new(io, MockExecutor::default());
+        assert!(block_on(shell.run()).is_err());
+        let output = std::string::String::from_utf8(shell.io.output).unwrap();
+        (output, shell.executor.executed)
+    }
+
+    #[test]
+    fn test_execute() {
+        let (_, executed) = run(&[b"add-bridge 1 NANO_A7\r", b"clear\r"]);
+        assert_eq!(executed, [Instruction::AddBridge(Node::_1, Node::NANO_A7), Instruction::Clear]);
+    }
+
+    #[test]
+    fn test_input_split_across_reads() {
+        let (_, executed) = run(&[b"add-br", b"idge 1 2", b"\r"]);
+        assert_eq!(executed, [Instruction::AddBridge(Node::_1, Node::_2)]);
+    }
+
+    #[test]
+    fn test_help() {
+        let (output, executed) = run(&[b"help\r"]);
+        assert!(output.contains("Available instructions:\r\n"));
+        assert!(executed.is_empty());
+    }
+
+    #[test]
+    fn test_errors_are_printed() {
+        let (output, executed) = run(&[b"add-bridge foo 1\r", b"add-bridge 1 foo\r", b"frobnicate\r"]);
+        assert!(output.contains("Error: invalid first node\r\n"));
+        assert!(output.contains("Error: invalid second node\r\n"));
+        assert!(output.contains("Error: no such instruction\r\n"));
+        assert!(executed.is_empty());
+    }
+
+    #[test]
+    fn test_prompt() {
+        let (output, _) = run(&[b"ab", b"\x1b[D"]);
+        assert_eq!(output, "\r> ab\x1b[K\r\x1b[4C\r> ab\x1b[K\r\x1b[3C");
+    }
+
+    #[test]
+    fn test_ctrl_c_clears_line() {
+        let (output, executed) = run(&[b"add-bridge 1\x03", b"clear\r"]);
+        assert!(output.contains("^C"));
+        assert_eq!(executed, [Instruction::Clear]);
+    }
+
+    #[test]
+    fn test_escape_sequences() {
+        // LEFT, DELETE (split across reads), then HOME and Ctrl+K
+        let (_, executed) = run(&[b"add-bridge 1 23\x1b[D\x1b[", b"3~\r", b"add-bridge 4 5\x1b[H\x0b\r"]);
+        assert_eq!(executed, [Instruction::AddBridge(Node::_1, Node::_2)]);
+    }
+
+    #[test]
+    fn test_history() {
+        let (_, executed) = run(&[b"add-bridge 1 2\r", b"clear\r", b"\x1b[A\x1b[A\r", b"\x10\x10\x10\x0e\r"]);
+        assert_eq!(
+            executed,
+            [
+                Instruction::AddBridge(Node::_1, Node::_2),
+                Instruction::Clear,
+                Instruction::AddBridge(Node::_1, Node::_2),
+                Instruction::Clear,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tab_completion() {
+        let (_, executed) = run(&[b"add-b\t", b"NANO_A\t", b"7\t5\r"]);
+        assert_eq!(executed, [Instruction::AddBridge(Node::NANO_A7, Node::_5)]);
+    }
+
+    #[test]
+    fn test_double_tab_lists_candidates() {
+        let (output, _) = run(&[b"add-bridge IS\t", b"\t"]);
+        assert!(output.contains("ISENSE_PLUS  "));
+        assert!(output.contains("ISENSE_MINUS  "));
+    }
+
+    #[test]
+    fn test_machine_protocol() {
+        let (output, executed) = run(&[b"machine\r", b"brightness\nhighlight-net 3\n", b"frob", b"nicate\n\nhelp\n"]);
+        let (_, replies) = output.split_once("OK\r\n").unwrap();
+        // no echo or prompt
+        assert_eq!(
+            replies.split_once("Available instructions:").unwrap().0,
+            "40%\r\nOK\r\nError: no such net\r\nERR\r\nError: no such instruction\r\nERR\r\n"
+        );
+        assert!(replies.ends_with("OK\r\n"));
+        assert_eq!(executed, [Instruction::PrintBrightness, Instruction::HighlightNet(3)]);
+    }
+
+    #[test]
+    fn test_machine_protocol_off() {
+        let (output, executed) = run(&[b"machine\r", b"machine off\r", b"clear\r"]);
+        assert!(output.contains("\r\nOK\r\nOK\r\n\r> "));
+        assert!(output.ends_with("\r> \x1b[K\r\x1b[2C"));
+        assert_eq!(executed, [Instruction::Clear]);
+    }
+
+    #[test]
+    fn test_machine_protocol_streaming() {
+        // the log is stopped by the next input, which is discarded
+        let (output, executed) = run(&[b"machine\r", b"measure current log\n", b"\n", b"brightness\n"]);
+        assert!(output.ends_with("OK\r\ntime_ms,current_ua,bus_voltage_mv\r\nOK\r\n40%\r\nOK\r\n"));
+        assert_eq!(executed, [Instruction::LogCurrent(100), Instruction::PrintBrightness]);
+    }
+
+    #[test]
+    fn test_machine_protocol_overflow() {
+        let (output, executed) = run(&[b"machine\r", &[b'x'; 40], &[b'x'; 40], b"\nclear\n"]);
+        assert!(output.ends_with("OK\r\nError: input buffer overflow\r\nERR\r\nOK\r\n"));
+        assert_eq!(executed, [Instruction::Clear]);
+    }
+}
@@ -10,6 +10,7 @@ fn main() -> Result<(), generator::Error> {
     println!("  {} Node ports", board_spec.node_ports.len());
     println!("  {} Lanes", board_spec.lanes.len());
     println!("  {} Bounce ports", board_spec.bounce_ports.len());
+    println!("  {} LEDs", board_spec.led_count());
     generator::sanity_check(&board_spec);
     println!("Sanity check passed ✔");
     let header = r#"
@@ -10,13 +10,16 @@ use crate::{
 use super::NodePort;
 
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
 
 #[derive(Debug)]
 pub enum Error {
     Io(std::io::Error),
     InvalidLaneEntry(u8),
     InvalidNodeEntry(u8),
+    InvalidLedEntry(u8),
     InvalidPort(String),
+    UnknownNode(String),
 }
 
 impl From<std::io::Error> for Error {
@@ -44,6 +47,66 @@ pub struct DynBoardSpec {
     pub node_ports: Vec<NodePort<DynNode>>,
     pub lanes: Vec<Lane>,
     pub bounce_ports: Vec<Port>,
+    pub leds: Vec<LedMapping>,
+}
+
+/// Group of LEDs that does not belong to a node
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LedSegment {
+    /// Positive top rail, on boards where it is not a node
+    TopRail,
+    /// Positive bottom rail, on boards where it is not a node
+    BottomRail,
+    /// All LEDs of the nano header. These may also be mapped to nodes.
+    Header,
+    Logo,
+}
+
+impl LedSegment {
+    fn parse(input: &str) -> Option<Self> {
+        match input {
+            "@TOP_RAIL" => Some(LedSegment::TopRail),
+            "@BOTTOM_RAIL" => Some(LedSegment::BottomRail),
+            "@HEADER" => Some(LedSegment::Header),
+            "@LOGO" => Some(LedSegment::Logo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LedTarget {
+    Node(DynNode),
+    Segment(LedSegment),
+}
+
+#[derive(Debug)]
+pub struct LedMapping(pub LedTarget, pub RangeInclusive<usize>);
+
+impl DynBoardSpec {
+    /// Number of LEDs on the board, i.e. one more than the highest mapped LED index
+    pub fn led_count(&self) -> usize {
+        self.leds.iter().map(|LedMapping(_, leds)| leds.end() + 1).max().unwrap_or(0)
+    }
+}
+
+fn parse_leds(input: &str) -> Option<RangeInclusive<usize>> {
+    if let Some((first, last)) = input.split_once("-") {
+        let first = first.parse().ok()?;
+        let last = last.parse().ok()?;
+        if first > last {
+            return None;
+        }
+        Some(first..=last)
+    } else {
+        let led = input.parse().ok()?;
+        Some(led..=led)
+    }
+}
+
+/// Lines which are empty or start with `#` are skipped in all board spec files
+fn is_comment_or_empty(line: &str) -> bool {
+    line.is_empty() || line.starts_with('#')
 }
 
 fn parse_port(input: &str) -> Result<Port, Error> {
@@ -58,6 +121,13 @@ pub fn parse<P: AsRef<Path>>(directory: P) -> Result<DynBoardSpec, Error> {
     let nodes_file = BufReader::new(File::open(directory.as_ref().join("nodes.txt"))?);
     let lanes_file = BufReader::new(File::open(directory.as_ref().join("lanes.txt"))?);
     let bounceports_file = BufReader::new(File::open(directory.as_ref().join("bounceports.txt"))?);
+    // optional: boards without an LED mapping have their LEDs turned off
+    let leds_path = directory.as_ref().join("leds.txt");
+    let leds_file = if leds_path.exists() {
+        Some(BufReader::new(File::open(leds_path)?))
+    } else {
+        None
+    };
 
     let mut node_counter = 0;
 
@@ -65,13 +135,14 @@ pub fn parse<P: AsRef<Path>>(directory: P) -> Result<DynBoardSpec, Error> {
     let mut node_ports = vec![];
     let mut lanes = vec![];
     let mut bounce_ports = vec![];
+    let mut leds = vec![];
 
     let mut i = 0;
 
     for line in nodes_file.lines() {
         i += 1;
         let line = line?;
-        if line.is_empty() {
+        if is_comment_or_empty(&line) {
             continue;
         }
         let (node_name, port_spec) = line.split_once(":").ok_or(Error::InvalidNodeEntry(i))?;
@@ -90,7 +161,7 @@ pub fn parse<P: AsRef<Path>>(directory: P) -> Result<DynBoardSpec, Error> {
     for line in lanes_file.lines() {
         i += 1;
         let line = line?;
-        if line.is_empty() {
+        if is_comment_or_empty(&line) {
             continue;
         }
         let (a, b) = line.split_once(":").ok_or(Error::InvalidLaneEntry(i))?;
@@ -99,13 +170,32 @@ pub fn parse<P: AsRef<Path>>(directory: P) -> Result<DynBoardSpec, Error> {
 
     for line in bounceports_file.lines() {
         let line = line?;
-        if line.is_empty() {
+        if is_comment_or_empty(&line) {
             continue;
         }
         bounce_ports.push(parse_port(&line)?);
     }
 
-    Ok(DynBoardSpec { nodes, node_ports, lanes, bounce_ports })
+    let mut i = 0;
+
+    for line in leds_file.into_iter().flat_map(|file| file.lines()) {
+        i += 1;
+        let line = line?;
+        if is_comment_or_empty(&line) {
+            continue;
+        }
+        let (name, led_spec) = line.split_once(":").ok_or(Error::InvalidLedEntry(i))?;
+        let target = if let Some(segment) = LedSegment::parse(name) {
+            LedTarget::Segment(segment)
+        } else if let Some(node) = nodes.get(name) {
+            LedTarget::Node(*node)
+        } else {
+            return Err(Error::UnknownNode(name.to_string()));
+        };
+        leds.push(LedMapping(target, parse_leds(led_spec).ok_or(Error::InvalidLedEntry(i))?));
+    }
+
+    Ok(DynBoardSpec { nodes, node_ports, lanes, bounce_ports, leds })
 }
 
 pub fn sanity_check(board_spec: &DynBoardSpec) {
@@ -141,6 +231,33 @@ pub fn sanity_check(board_spec: &DynBoardSpec) {
         }
         used_ports.insert(*port);
     }
+    let mut led_targets: Vec<Option<LedTarget>> = vec![None; board_spec.led_count()];
+    for LedMapping(target, leds) in &board_spec.leds {
+        if *target == LedTarget::Segment(LedSegment::Header) {
+            // the header segment overlaps with the nano nodes
+            continue;
+        }
+        for led in leds.clone() {
+            if let Some(other) = led_targets[led] {
+                problems.push(format!(
+                    "LED {led} used more than once (by {other:?} and {target:?})"
+                ));
+            }
+            led_targets[led] = Some(*target);
+        }
+    }
+    for LedMapping(target, leds) in &board_spec.leds {
+        if *target == LedTarget::Segment(LedSegment::Header) {
+            for led in leds.clone() {
+                led_targets[led].get_or_insert(*target);
+            }
+        }
+    }
+    for (led, target) in led_targets.iter().enumerate() {
+        if target.is_none() {
+            problems.push(format!("LED {led} is not mapped"));
+        }
+    }
     for problem in &problems {
         println!("Found problem: {}", problem);
     }
@@ -167,6 +284,7 @@ pub fn generate_board_spec_code(board_spec: &DynBoardSpec) -> TokenStream {
     let mut variants = vec![];
     let mut as_str_arms = vec![];
     let mut from_str_arms = vec![];
+    let mut all_nodes = vec![];
 
     let mut node_tokens = HashMap::new();
 
@@ -183,6 +301,7 @@ pub fn generate_board_spec_code(board_spec: &DynBoardSpec) -> TokenStream {
         variants.push(quote!(#variant_token = #id));
         as_str_arms.push(quote!(#full_variant => #name_lit));
         from_str_arms.push(quote!(#name_lit => Ok(#full_variant)));
+        all_nodes.push(full_variant.clone());
         node_tokens.insert(id, full_variant);
     }
 
@@ -219,6 +338,47 @@ pub fn generate_board_spec_code(board_spec: &DynBoardSpec) -> TokenStream {
 
     let node_count_u8 = node_count as u8;
 
+    let led_count = board_spec.led_count();
+
+    let mut node_leds: Vec<(u8, Vec<usize>)> = vec![];
+    for LedMapping(target, leds) in &board_spec.leds {
+        if let LedTarget::Node(node) = target {
+            if let Some((_, node_leds)) = node_leds.iter_mut().find(|(id, _)| *id == node.id()) {
+                node_leds.extend(leds.clone());
+            } else {
+                node_leds.push((node.id(), leds.clone().collect()));
+            }
+        }
+    }
+    node_leds.sort_by_key(|(id, _)| *id);
+    let leds_arms = node_leds.iter().map(|(id, leds)| {
+        let node = node_tokens.get(id);
+        quote!(#node => &[#(#leds),*])
+    });
+    let leds_body = if node_leds.is_empty() {
+        quote!(&[])
+    } else {
+        quote!(
+            match self {
+                #(#leds_arms),*,
+                _ => &[],
+            }
+        )
+    };
+
+    let segment_leds = |segment: LedSegment| -> Vec<usize> {
+        board_spec
+            .leds
+            .iter()
+            .filter(|LedMapping(target, _)| *target == LedTarget::Segment(segment))
+            .flat_map(|LedMapping(_, leds)| leds.clone())
+            .collect()
+    };
+    let top_rail_leds = segment_leds(LedSegment::TopRail);
+    let bottom_rail_leds = segment_leds(LedSegment::BottomRail);
+    let header_leds = segment_leds(LedSegment::Header);
+    let logo_leds = segment_leds(LedSegment::Logo);
+
     quote!(
         use jumperless_types::{
             Node as NodeTrait,
@@ -250,11 +410,25 @@ pub fn generate_board_spec_code(board_spec: &DynBoardSpec) -> TokenStream {
         }
 
         impl Node {
+            pub const ALL: [Node; #node_count] = [#(#all_nodes),*];
+
             pub fn as_str(&self) -> &'static str {
                 match self {
                     #(#as_str_arms),*
                 }
             }
+
+            pub fn leds(&self) -> &'static [usize] {
+                #leds_body
+            }
+        }
+
+        pub mod leds {
+            pub const COUNT: usize = #led_count;
+            pub const TOP_RAIL: &[usize] = &[#(#top_rail_leds),*];
+            pub const BOTTOM_RAIL: &[usize] = &[#(#bottom_rail_leds),*];
+            pub const HEADER: &[usize] = &[#(#header_leds),*];
+            pub const LOGO: &[usize] = &[#(#logo_leds),*];
         }
 
         #[derive(Debug)]
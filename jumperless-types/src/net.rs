@@ -1,6 +1,6 @@
 use crate::{NetId, Node, set::NodeSet};
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Net<N: Node> {
     pub id: NetId,
     pub nodes: NodeSet<N>,
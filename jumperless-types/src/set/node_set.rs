@@ -64,6 +64,15 @@ impl<N: Node> NodeSet<N> {
     }
 }
 
+// implemented manually, since deriving would require `N: Copy`
+impl<N> Clone for NodeSet<N> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<N> Copy for NodeSet<N> {}
+
 impl<N: Node> FromIterator<N> for NodeSet<N> {
     fn from_iter<T: IntoIterator<Item = N>>(iter: T) -> Self {
         let mut set = Self::default();
@@ -0,0 +1,76 @@
+/// Outcome of [`complete`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Completion<'a> {
+    /// No candidate starts with the given prefix
+    None,
+    /// Exactly one candidate starts with the given prefix
+    Unique(&'a str),
+    /// Multiple candidates start with the given prefix
+    ///
+    /// Contains the longest prefix they all have in common.
+    Ambiguous(&'a str),
+}
+
+/// Complete `prefix` to one of the given `candidates`
+///
+/// # Examples
+///
+/// ```
+/// # use line_buffer::{complete, Completion};
+/// let commands = ["help", "reset", "rainbow-bounce"];
+/// assert_eq!(complete("he", commands), Completion::Unique("help"));
+/// assert_eq!(complete("r", commands), Completion::Ambiguous("r"));
+/// assert_eq!(complete("x", commands), Completion::None);
+/// ```
+pub fn complete<'a>(prefix: &str, candidates: impl IntoIterator<Item = &'a str>) -> Completion<'a> {
+    let mut matches = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(prefix));
+    let Some(first) = matches.next() else {
+        return Completion::None;
+    };
+    let mut common = first;
+    let mut unique = true;
+    for candidate in matches {
+        unique = false;
+        let len = common
+            .bytes()
+            .zip(candidate.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        common = &common[..len];
+    }
+    if unique {
+        Completion::Unique(first)
+    } else {
+        Completion::Ambiguous(common)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODES: [&str; 5] = ["NANO_A0", "NANO_A1", "NANO_D13", "ISENSE_PLUS", "ISENSE_MINUS"];
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(complete("NA", NODES), Completion::Ambiguous("NANO_"));
+        assert_eq!(complete("NANO_A", NODES), Completion::Ambiguous("NANO_A"));
+        assert_eq!(complete("I", NODES), Completion::Ambiguous("ISENSE_"));
+        assert_eq!(complete("", NODES), Completion::Ambiguous(""));
+    }
+
+    #[test]
+    fn test_unique() {
+        assert_eq!(complete("NANO_D", NODES), Completion::Unique("NANO_D13"));
+        assert_eq!(complete("ISENSE_M", NODES), Completion::Unique("ISENSE_MINUS"));
+        assert_eq!(complete("NANO_A1", NODES), Completion::Unique("NANO_A1"));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert_eq!(complete("nano", NODES), Completion::None);
+        assert_eq!(complete("NANO_A12", NODES), Completion::None);
+    }
+}
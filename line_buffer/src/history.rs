@@ -0,0 +1,153 @@
+use heapless::{Deque, Vec};
+
+use crate::LineBuffer;
+
+/// Fixed-capacity history of submitted lines
+///
+/// Holds up to `DEPTH` lines, each up to `SIZE` bytes long. When full, pushing a new
+/// line drops the oldest one.
+///
+/// The history can be browsed with [`History::previous`] and [`History::next`], which
+/// replace the content of a [`LineBuffer`]. The line that was being edited before browsing
+/// started is kept, and restored when moving past the most recent entry again.
+pub struct History<const SIZE: usize, const DEPTH: usize> {
+    entries: Deque<Vec<u8, SIZE>, DEPTH>,
+    // 0 while not browsing, otherwise `n` refers to the n-th most recent entry
+    position: usize,
+    draft: Vec<u8, SIZE>,
+}
+
+impl<const SIZE: usize, const DEPTH: usize> History<SIZE, DEPTH> {
+    pub fn new() -> Self {
+        Self {
+            entries: Deque::new(),
+            position: 0,
+            draft: Vec::new(),
+        }
+    }
+
+    /// Number of entries in the history
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add a line to the history, and stop browsing
+    ///
+    /// Empty lines, and lines identical to the most recent entry are not added.
+    pub fn push(&mut self, line: &[u8]) {
+        self.position = 0;
+        if line.is_empty() || self.entries.back().map(|last| &last[..]) == Some(line) {
+            return;
+        }
+        if self.entries.is_full() {
+            self.entries.pop_front();
+        }
+        let len = line.len().min(SIZE);
+        let mut entry = Vec::new();
+        _ = entry.extend_from_slice(&line[..len]);
+        _ = self.entries.push_back(entry);
+    }
+
+    /// Returns the n-th most recent entry (starting at 1)
+    pub fn get(&self, n: usize) -> Option<&[u8]> {
+        if n == 0 {
+            return None;
+        }
+        self.entries.iter().rev().nth(n - 1).map(|entry| &entry[..])
+    }
+
+    /// Replace the buffer's content with the next older entry
+    ///
+    /// Ignored if there is no older entry.
+    pub fn previous(&mut self, buffer: &mut LineBuffer<SIZE>) {
+        if self.position == self.entries.len() {
+            return;
+        }
+        if self.position == 0 {
+            self.draft.clear();
+            _ = self.draft.extend_from_slice(buffer.content());
+        }
+        self.position += 1;
+        if let Some(entry) = self.get(self.position) {
+            buffer.set_content(entry);
+        }
+    }
+
+    /// Replace the buffer's content with the next newer entry
+    ///
+    /// Moving past the most recent entry restores the line that was being edited
+    /// before browsing started. Ignored if not browsing.
+    pub fn next(&mut self, buffer: &mut LineBuffer<SIZE>) {
+        if self.position == 0 {
+            return;
+        }
+        self.position -= 1;
+        if let Some(entry) = self.get(self.position) {
+            buffer.set_content(entry);
+        } else {
+            buffer.set_content(&self.draft);
+        }
+    }
+}
+
+impl<const SIZE: usize, const DEPTH: usize> Default for History<SIZE, DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(lines: &[&[u8]]) -> History<16, 3> {
+        let mut history = History::new();
+        for line in lines {
+            history.push(line);
+        }
+        history
+    }
+
+    #[test]
+    fn test_push_drops_oldest_entry() {
+        let history = history(&[b"one", b"two", b"three", b"four"]);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(1), Some(&b"four"[..]));
+        assert_eq!(history.get(3), Some(&b"two"[..]));
+        assert_eq!(history.get(4), None);
+    }
+
+    #[test]
+    fn test_push_skips_empty_and_repeated_lines() {
+        let history = history(&[b"one", b"", b"one"]);
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_browse() {
+        let mut history = history(&[b"one", b"two"]);
+        let mut buf: LineBuffer<16> = LineBuffer::new();
+        buf.insert(b'x').unwrap();
+
+        history.previous(&mut buf);
+        assert_eq!(buf.content(), b"two");
+        assert_eq!(buf.cursor(), 3);
+        history.previous(&mut buf);
+        assert_eq!(buf.content(), b"one");
+        // no older entry
+        history.previous(&mut buf);
+        assert_eq!(buf.content(), b"one");
+
+        history.next(&mut buf);
+        assert_eq!(buf.content(), b"two");
+        // back to the line that was edited before
+        history.next(&mut buf);
+        assert_eq!(buf.content(), b"x");
+        history.next(&mut buf);
+        assert_eq!(buf.content(), b"x");
+    }
+}
@@ -2,9 +2,21 @@
 
 use heapless::Vec;
 
+mod history;
+pub use history::History;
+
+mod completion;
+pub use completion::{complete, Completion};
+
+/// Editable line of input, with a cursor
+///
+/// Text removed with one of the `kill_*` methods is kept in a kill buffer, from where
+/// it can be inserted again with [`LineBuffer::yank`]. The kill buffer survives [`LineBuffer::reset`],
+/// so killed text can be moved from one line to the next.
 pub struct LineBuffer<const SIZE: usize> {
     buffer: Vec<u8, SIZE>,
     cursor_offset: usize,
+    kill_buffer: Vec<u8, SIZE>,
 }
 
 impl<const SIZE: usize> LineBuffer<SIZE> {
@@ -12,10 +24,11 @@ impl<const SIZE: usize> LineBuffer<SIZE> {
         Self {
             buffer: Vec::new(),
             cursor_offset: 0,
+            kill_buffer: Vec::new(),
         }
     }
 
-    /// Reset to initial state
+    /// Reset to initial state (except for the kill buffer)
     pub fn reset(&mut self) {
         self.buffer.clear();
         self.cursor_offset = 0;
@@ -26,11 +39,29 @@ impl<const SIZE: usize> LineBuffer<SIZE> {
         &self.buffer
     }
 
+    /// Replace buffer contents, placing the cursor at the end
+    ///
+    /// Content that does not fit into the buffer is cut off.
+    pub fn set_content(&mut self, content: &[u8]) {
+        self.reset();
+        let len = content.len().min(SIZE);
+        _ = self.buffer.extend_from_slice(&content[..len]);
+    }
+
     /// Returns cursor position (from the left)
     pub fn cursor(&self) -> usize {
         self.buffer.len() - self.cursor_offset
     }
 
+    /// Returns the part of the current word that is left of the cursor
+    ///
+    /// Words are separated by spaces.
+    pub fn word_before_cursor(&self) -> &[u8] {
+        let before = &self.buffer[..self.cursor()];
+        let start = before.iter().rposition(|c| *c == b' ').map(|i| i + 1).unwrap_or(0);
+        &before[start..]
+    }
+
     /// Insert given character at current position
     ///
     /// Fails if the buffer is full.
@@ -49,6 +80,49 @@ impl<const SIZE: usize> LineBuffer<SIZE> {
         }
     }
 
+    /// Deletes character right of the cursor
+    ///
+    /// Ignored, if the cursor is at the very right
+    pub fn delete(&mut self) {
+        if self.cursor_offset > 0 {
+            self.buffer.remove(self.cursor());
+            self.cursor_offset -= 1;
+        }
+    }
+
+    /// Deletes from the beginning of the current (or previous) word up to the cursor
+    ///
+    /// The deleted text is placed in the kill buffer.
+    pub fn kill_word_left(&mut self) {
+        self.kill(self.word_start(), self.cursor());
+    }
+
+    /// Deletes everything right of the cursor
+    ///
+    /// The deleted text is placed in the kill buffer.
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor(), self.buffer.len());
+    }
+
+    /// Deletes everything left of the cursor
+    ///
+    /// The deleted text is placed in the kill buffer.
+    pub fn kill_to_start(&mut self) {
+        self.kill(0, self.cursor());
+    }
+
+    /// Insert the content of the kill buffer at the cursor
+    ///
+    /// If the buffer fills up, the inserted text is cut off.
+    pub fn yank(&mut self) {
+        let cursor = self.cursor();
+        let old_len = self.buffer.len();
+        let len = self.kill_buffer.len().min(SIZE - old_len);
+        _ = self.buffer.resize(old_len + len, 0);
+        self.buffer.copy_within(cursor..old_len, cursor + len);
+        self.buffer[cursor..cursor + len].copy_from_slice(&self.kill_buffer[..len]);
+    }
+
     /// Move the cursor to the left
     pub fn move_left(&mut self) {
         self.cursor_offset = (self.cursor_offset + 1).min(self.buffer.len());
@@ -71,6 +145,57 @@ impl<const SIZE: usize> LineBuffer<SIZE> {
         self.cursor_offset = 0;
     }
 
+    /// Move cursor to the beginning of the current (or previous) word
+    pub fn move_word_left(&mut self) {
+        self.set_cursor(self.word_start());
+    }
+
+    /// Move cursor to the end of the current (or next) word
+    pub fn move_word_right(&mut self) {
+        self.set_cursor(self.word_end());
+    }
+
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor();
+        while i > 0 && self.buffer[i - 1] == b' ' {
+            i -= 1;
+        }
+        while i > 0 && self.buffer[i - 1] != b' ' {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor();
+        while i < self.buffer.len() && self.buffer[i] == b' ' {
+            i += 1;
+        }
+        while i < self.buffer.len() && self.buffer[i] != b' ' {
+            i += 1;
+        }
+        i
+    }
+
+    /// Remove the text between `start` and `end`, placing it in the kill buffer
+    ///
+    /// The kill buffer is left untouched, if there is nothing to remove.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.kill_buffer.clear();
+        _ = self.kill_buffer.extend_from_slice(&self.buffer[start..end]);
+        let len = self.buffer.len();
+        self.buffer.copy_within(end..len, start);
+        self.buffer.truncate(len - (end - start));
+        self.set_cursor(start);
+    }
+
+    fn set_cursor(&mut self, position: usize) {
+        self.cursor_offset = self.buffer.len() - position;
+    }
+
     fn fix_cursor(&mut self) {
         if self.cursor_offset > self.buffer.len() {
             self.cursor_offset = self.buffer.len();
@@ -127,4 +252,134 @@ mod tests {
         assert_eq!(buf.cursor(), 2);
         assert_eq!(buf.content(), b"hello");
     }
+
+    #[test]
+    fn test_delete() {
+        let mut buf: LineBuffer<64> = LineBuffer::new();
+
+        buf.set_content(b"hello");
+        // nothing right of the cursor
+        buf.delete();
+        assert_eq!(buf.content(), b"hello");
+
+        buf.move_home();
+        buf.delete();
+        assert_eq!(buf.content(), b"ello");
+        assert_eq!(buf.cursor(), 0);
+    }
+
+    #[test]
+    fn test_word_movement() {
+        let mut buf: LineBuffer<64> = LineBuffer::new();
+
+        buf.set_content(b"add-bridge  NANO_D13 42");
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 21);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 12);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 0);
+        buf.move_word_left();
+        assert_eq!(buf.cursor(), 0);
+
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 10);
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 20);
+        buf.move_word_right();
+        buf.move_word_right();
+        assert_eq!(buf.cursor(), 23);
+    }
+
+    #[test]
+    fn test_kill_word_and_yank() {
+        let mut buf: LineBuffer<64> = LineBuffer::new();
+
+        buf.set_content(b"add-bridge NANO_D13 42");
+        buf.move_left();
+        buf.move_left();
+        buf.move_left();
+        buf.kill_word_left();
+        assert_eq!(buf.content(), b"add-bridge  42");
+        assert_eq!(buf.cursor(), 11);
+
+        buf.move_end();
+        buf.yank();
+        assert_eq!(buf.content(), b"add-bridge  42NANO_D13");
+        assert_eq!(buf.cursor(), 22);
+
+        // the kill buffer survives a reset
+        buf.reset();
+        buf.yank();
+        assert_eq!(buf.content(), b"NANO_D13");
+    }
+
+    #[test]
+    fn test_kill_to_end_and_start() {
+        let mut buf: LineBuffer<64> = LineBuffer::new();
+
+        buf.set_content(b"hello world");
+        buf.move_word_left();
+        buf.kill_to_end();
+        assert_eq!(buf.content(), b"hello ");
+        assert_eq!(buf.cursor(), 6);
+
+        buf.move_left();
+        buf.kill_to_start();
+        assert_eq!(buf.content(), b" ");
+        assert_eq!(buf.cursor(), 0);
+
+        // killing nothing keeps the kill buffer
+        buf.kill_to_start();
+        buf.move_end();
+        buf.yank();
+        assert_eq!(buf.content(), b" hello");
+    }
+
+    #[test]
+    fn test_yank_overflow() {
+        let mut buf: LineBuffer<8> = LineBuffer::new();
+
+        buf.set_content(b"abcdef");
+        buf.kill_to_start();
+        buf.set_content(b"xyz");
+        buf.move_left();
+        buf.yank();
+        assert_eq!(buf.content(), b"xyabcdez");
+        assert_eq!(buf.cursor(), 7);
+    }
+
+    #[test]
+    fn test_word_before_cursor() {
+        let mut buf: LineBuffer<64> = LineBuffer::new();
+        assert_eq!(buf.word_before_cursor(), b"");
+
+        buf.set_content(b"add-bridge NANO_D13 42");
+        assert_eq!(buf.word_before_cursor(), b"42");
+        buf.move_left();
+        buf.move_left();
+        assert_eq!(buf.word_before_cursor(), b"");
+        buf.move_left();
+        assert_eq!(buf.word_before_cursor(), b"NANO_D13");
+        buf.move_left();
+        buf.move_left();
+        assert_eq!(buf.word_before_cursor(), b"NANO_D");
+        buf.move_home();
+        assert_eq!(buf.word_before_cursor(), b"");
+    }
+
+    #[test]
+    fn test_set_content() {
+        let mut buf: LineBuffer<4> = LineBuffer::new();
+
+        buf.insert(b'x').unwrap();
+        buf.move_home();
+        buf.set_content(b"abc");
+        assert_eq!(buf.cursor(), 3);
+        assert_eq!(buf.content(), b"abc");
+
+        buf.set_content(b"too long");
+        assert_eq!(buf.cursor(), 4);
+        assert_eq!(buf.content(), b"too ");
+    }
 }